@@ -0,0 +1,57 @@
+use elk_bledom_controller::ambilight::{average_color, AverageColorStreamer};
+use elk_bledom_controller::device::{BledomDevice, BledomError};
+use log::{error, info};
+use std::time::Instant;
+use tokio::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 36;
+
+// Stand-in for a screen grab: a gradient that slowly scrolls over time. Swap this for a
+// platform capture API (or decoded video frames) to get a real ambilight.
+fn capture_frame(started: Instant) -> Vec<(u8, u8, u8)> {
+    let offset = (started.elapsed().as_millis() / 20) as u32;
+    (0..WIDTH * HEIGHT)
+        .map(|i| {
+            let x = (i % WIDTH + offset) % 256;
+            let y = (i / WIDTH) * 255 / HEIGHT;
+            (x as u8, y as u8, (255 - x) as u8)
+        })
+        .collect()
+}
+
+#[tokio::main]
+async fn main() -> Result<(), BledomError> {
+    env_logger::init();
+
+    let device = match BledomDevice::builder().build().await {
+        Ok(dev) => dev,
+        Err(e) => {
+            error!("failed to initialize BledomDevice: {}", e);
+            return Err(e);
+        }
+    };
+    device.power_on().await?;
+
+    let cancel = CancellationToken::new();
+    let on_ctrl_c = cancel.clone();
+    tokio::spawn(async move {
+        tokio::signal::ctrl_c().await.ok();
+        on_ctrl_c.cancel();
+    });
+
+    let started = Instant::now();
+    let mut streamer = AverageColorStreamer::new(
+        || average_color(capture_frame(started)).unwrap_or((0, 0, 0)),
+        Duration::from_millis(100),
+    );
+    info!(
+        "streaming average color every {:?}, press Ctrl-C to stop",
+        streamer.interval()
+    );
+    streamer.run(&device, &cancel).await?;
+
+    info!("turning off the light...");
+    device.power_off().await
+}
@@ -0,0 +1,27 @@
+// Controls a strip through `bridge_server` running elsewhere; the device API is the same as
+// for a local strip. Connects to localhost:7878 unless BRIDGE_ADDRESS says otherwise.
+use elk_bledom_controller::device::{BledomDevice, BledomError};
+use elk_bledom_controller::remote::RemoteSink;
+use log::info;
+use tokio::time::{self, Duration};
+
+#[tokio::main]
+async fn main() -> Result<(), BledomError> {
+    env_logger::init();
+
+    let address = std::env::var("BRIDGE_ADDRESS").unwrap_or_else(|_| "localhost:7878".to_string());
+    let sink = RemoteSink::connect(address).await?;
+    // The bridge already waits its command delay before answering each write.
+    let device = BledomDevice::builder()
+        .command_delay_ms(0)
+        .build_with_sink(sink);
+
+    info!("turning on the light...");
+    device.power_on().await?;
+    device.set_color_hex("#ff8800").await?;
+    time::sleep(Duration::from_secs(2)).await;
+    device.set_brightness(30).await?;
+    time::sleep(Duration::from_secs(2)).await;
+    info!("turning off the light...");
+    device.power_off().await
+}
@@ -0,0 +1,37 @@
+// Owns the BLE connection and serves it to `bridge_client`s over TCP, e.g. on a Raspberry Pi
+// next to the strip. Listens on [::]:7878 unless BRIDGE_LISTEN says otherwise.
+use elk_bledom_controller::device::{BledomDevice, BledomError};
+use elk_bledom_controller::remote;
+use log::{error, info};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+#[tokio::main]
+async fn main() -> Result<(), BledomError> {
+    env_logger::init();
+
+    let listen = std::env::var("BRIDGE_LISTEN").unwrap_or_else(|_| "[::]:7878".to_string());
+    let device = Arc::new(BledomDevice::builder().auto_reconnect(true).build().await?);
+    let listener = TcpListener::bind(&listen)
+        .await
+        .map_err(|e| BledomError::Other(Box::new(e)))?;
+    info!("bridging {} on {listen}", device.address());
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                error!("failed to accept connection: {}", e);
+                continue;
+            }
+        };
+        info!("client {peer} connected");
+        let device = Arc::clone(&device);
+        tokio::spawn(async move {
+            if let Err(e) = remote::serve(stream, &device).await {
+                error!("client {peer}: {}", e);
+            }
+            info!("client {peer} disconnected");
+        });
+    }
+}
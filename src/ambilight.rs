@@ -0,0 +1,61 @@
+use crate::device::{BledomDevice, BledomError, CMD_DELAY};
+use crate::sink::CommandSink;
+use std::time::Duration;
+use tokio::time::{self, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+
+// Averages a frame of pixels; `None` for an empty frame.
+pub fn average_color(pixels: impl IntoIterator<Item = (u8, u8, u8)>) -> Option<(u8, u8, u8)> {
+    let (mut r, mut g, mut b, mut count) = (0u64, 0u64, 0u64, 0u64);
+    for (pr, pg, pb) in pixels {
+        r += u64::from(pr);
+        g += u64::from(pg);
+        b += u64::from(pb);
+        count += 1;
+    }
+    (count > 0).then(|| ((r / count) as u8, (g / count) as u8, (b / count) as u8))
+}
+
+// Polls `sample` at a fixed rate and pushes the result with `set_color`, skipping colors equal
+// to the last one sent. The interval is never shorter than the default command delay, and
+// slow writes drop ticks instead of queueing them.
+pub struct AverageColorStreamer<F> {
+    sample: F,
+    interval: Duration,
+}
+
+impl<F: FnMut() -> (u8, u8, u8)> AverageColorStreamer<F> {
+    pub fn new(sample: F, interval: Duration) -> Self {
+        Self {
+            sample,
+            interval: interval.max(CMD_DELAY),
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    // Runs until `cancel` fires or a write fails.
+    pub async fn run<S: CommandSink>(
+        &mut self,
+        device: &BledomDevice<S>,
+        cancel: &CancellationToken,
+    ) -> Result<(), BledomError> {
+        let mut ticker = time::interval(self.interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut last = None;
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => return Ok(()),
+                _ = ticker.tick() => {}
+            }
+            let color = (self.sample)();
+            if last == Some(color) {
+                continue;
+            }
+            device.set_color(color.0, color.1, color.2).await?;
+            last = Some(color);
+        }
+    }
+}
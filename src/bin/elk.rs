@@ -0,0 +1,92 @@
+use elk_bledom_controller::device::{BledomDevice, BledomError, Effect};
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: elk <command> [argument]
+
+commands:
+    on                  turn the light on
+    off                 turn the light off
+    color <RRGGBB>      set a static color, e.g. ff0000 or #f00
+    brightness <0-100>  set the brightness
+    effect <name>       run a built-in effect, e.g. blink_red
+    speed <0-100>       set the effect speed
+    effects             list the available effect names";
+
+enum Command {
+    On,
+    Off,
+    Color(String),
+    Brightness(u8),
+    Effect(Effect),
+    Speed(u8),
+}
+
+fn parse_percent(arg: Option<String>, what: &str) -> Result<u8, String> {
+    let arg = arg.ok_or_else(|| format!("missing {what} value"))?;
+    arg.parse()
+        .map_err(|_| format!("invalid {what} value {arg:?} (expected 0-100)"))
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let command = args.next().ok_or("missing command")?;
+    let argument = args.next();
+    let parsed = match command.as_str() {
+        "on" => Command::On,
+        "off" => Command::Off,
+        "color" => Command::Color(argument.ok_or("missing color value")?),
+        "brightness" => Command::Brightness(parse_percent(argument, "brightness")?),
+        "speed" => Command::Speed(parse_percent(argument, "speed")?),
+        "effect" => {
+            let name = argument.ok_or("missing effect name")?;
+            let effect = Effect::all()
+                .iter()
+                .find(|e| e.to_string() == name)
+                .ok_or_else(|| format!("unknown effect {name:?} (see `elk effects`)"))?;
+            Command::Effect(*effect)
+        }
+        other => return Err(format!("unknown command {other:?}")),
+    };
+    if args.next().is_some() {
+        return Err("too many arguments".to_string());
+    }
+    Ok(parsed)
+}
+
+async fn run(command: Command) -> Result<(), BledomError> {
+    let device = BledomDevice::builder().build().await?;
+    match command {
+        Command::On => device.power_on().await,
+        Command::Off => device.power_off().await,
+        Command::Color(hex) => device.set_color_hex(&hex).await,
+        Command::Brightness(value) => device.set_brightness(value).await,
+        Command::Effect(effect) => device.set_effect_typed(effect).await,
+        Command::Speed(value) => device.set_effect_speed(value).await,
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("effects") {
+        for effect in Effect::all() {
+            println!("{effect}");
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    let command = match parse_args(args) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("elk: {e}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(command).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("elk: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
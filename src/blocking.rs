@@ -0,0 +1,88 @@
+use crate::device::{BledomDevice, BledomDeviceBuilder, BledomError, Effect};
+use std::future::Future;
+use tokio::runtime::{Builder, Runtime};
+
+// Owns a runtime and blocks on each call, for callers without an async runtime. It has one
+// worker thread, so background tasks like the keepalive keep running between calls. Must not
+// be used from inside another tokio runtime.
+#[derive(Debug)]
+pub struct BledomDeviceSync {
+    runtime: Runtime,
+    device: BledomDevice,
+}
+
+impl BledomDeviceSync {
+    pub fn connect() -> Result<Self, BledomError> {
+        Self::from_builder(BledomDevice::builder())
+    }
+
+    pub fn from_builder(builder: BledomDeviceBuilder) -> Result<Self, BledomError> {
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .map_err(|e| BledomError::Other(Box::new(e)))?;
+        let device = runtime.block_on(builder.build())?;
+        Ok(Self { runtime, device })
+    }
+
+    pub fn device(&self) -> &BledomDevice {
+        &self.device
+    }
+
+    // Runs any other async call on the wrapped device, e.g.
+    // `sync.block_on(sync.device().set_color_hsv(120.0, 1.0, 1.0))`.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    pub fn disconnect(&self) -> Result<(), BledomError> {
+        self.block_on(self.device.disconnect())
+    }
+
+    pub fn power_on(&self) -> Result<(), BledomError> {
+        self.block_on(self.device.power_on())
+    }
+
+    pub fn power_off(&self) -> Result<(), BledomError> {
+        self.block_on(self.device.power_off())
+    }
+
+    pub fn toggle(&self) -> Result<(), BledomError> {
+        self.block_on(self.device.toggle())
+    }
+
+    pub fn set_brightness(&self, value: u8) -> Result<(), BledomError> {
+        self.block_on(self.device.set_brightness(value))
+    }
+
+    pub fn set_color(
+        &self,
+        red_value: u8,
+        green_value: u8,
+        blue_value: u8,
+    ) -> Result<(), BledomError> {
+        self.block_on(self.device.set_color(red_value, green_value, blue_value))
+    }
+
+    pub fn set_color_hex(&self, hex: &str) -> Result<(), BledomError> {
+        self.block_on(self.device.set_color_hex(hex))
+    }
+
+    pub fn set_effect(&self, value: u8) -> Result<(), BledomError> {
+        self.block_on(self.device.set_effect(value))
+    }
+
+    pub fn set_effect_typed(&self, effect: Effect) -> Result<(), BledomError> {
+        self.block_on(self.device.set_effect_typed(effect))
+    }
+
+    pub fn set_effect_speed(&self, value: u8) -> Result<(), BledomError> {
+        self.block_on(self.device.set_effect_speed(value))
+    }
+
+    #[cfg(feature = "time")]
+    pub fn sync_time(&self) -> Result<(), BledomError> {
+        self.block_on(self.device.sync_time())
+    }
+}
@@ -0,0 +1,209 @@
+use crate::device::{BledomError, Schedule, ScheduleKind, PACKET_END, PACKET_START, WEEK_DAYS};
+
+// Mode byte of the color command (byte 3) selecting plain red/green/blue. What other values
+// mean isn't documented anywhere; 0x01 is the white channel of RGBW strips (`SetWhite`), and
+// some units only take RGB with 0x00.
+pub const COLOR_MODE_RGB: u8 = 0x03;
+
+// The semantic operation behind a framed 9-byte packet, e.g. for proxies and loggers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    PowerOn,
+    PowerOff,
+    SetColor(u8, u8, u8),
+    // The color command with a mode byte other than the usual 0x03, for strips that expect
+    // e.g. 0x00 there; see `BledomDeviceBuilder::color_mode_byte`. Modes 0x03 and 0x01 are
+    // rejected, as those packets decode as `SetColor` and `SetWhite`.
+    SetColorWithMode {
+        mode: u8,
+        red: u8,
+        green: u8,
+        blue: u8,
+    },
+    SetWhite(u8),
+    SetBrightness(u8),
+    SetEffect(u8),
+    SetEffectSpeed(u8),
+    SetTime {
+        hour: u8,
+        minute: u8,
+        second: u8,
+        day_of_week: u8,
+    },
+    SetSchedule {
+        kind: ScheduleKind,
+        schedule: Schedule,
+    },
+    EnableMic(bool),
+    SetMusicMode(u8),
+    SetMusicSensitivity(u8),
+}
+
+impl Command {
+    pub fn encode(&self) -> [u8; 9] {
+        let payload = match *self {
+            Command::PowerOn => [0x00, 0x04, 0xf0, 0x00, 0x01, 0xff, 0x00],
+            Command::PowerOff => [0x00, 0x04, 0x00, 0x00, 0x00, 0xff, 0x00],
+            Command::SetColor(r, g, b) => [0x00, 0x05, COLOR_MODE_RGB, r, g, b, 0x00],
+            Command::SetColorWithMode {
+                mode,
+                red,
+                green,
+                blue,
+            } => [0x00, 0x05, mode, red, green, blue, 0x00],
+            Command::SetWhite(value) => [0x00, 0x05, 0x01, value, 0x00, 0x00, 0x00],
+            Command::SetBrightness(value) => [0x00, 0x01, value, 0x00, 0x00, 0x00, 0x00],
+            Command::SetEffect(value) => [0x00, 0x03, value, 0x03, 0x00, 0x00, 0x00],
+            Command::SetEffectSpeed(value) => [0x00, 0x02, value, 0x00, 0x00, 0x00, 0x00],
+            Command::SetTime {
+                hour,
+                minute,
+                second,
+                day_of_week,
+            } => [0x00, 0x83, hour, minute, second, day_of_week, 0x00],
+            Command::SetSchedule { kind, schedule } => {
+                let kind = match kind {
+                    ScheduleKind::On => 0x00,
+                    ScheduleKind::Off => 0x01,
+                };
+                let value = if schedule.enabled {
+                    schedule.days | 0x80
+                } else {
+                    schedule.days
+                };
+                [
+                    0x00,
+                    0x82,
+                    schedule.hour,
+                    schedule.minute,
+                    0x00,
+                    kind,
+                    value,
+                ]
+            }
+            Command::EnableMic(on) => [0x04, 0x07, on as u8, 0xff, 0xff, 0xff, 0x00],
+            Command::SetMusicMode(mode) => [0x05, 0x03, mode, 0x04, 0xff, 0xff, 0x00],
+            Command::SetMusicSensitivity(value) => [0x04, 0x06, value, 0xff, 0xff, 0xff, 0x00],
+        };
+        let mut data = [PACKET_START, 0, 0, 0, 0, 0, 0, 0, PACKET_END];
+        data[1..8].copy_from_slice(&payload);
+        data
+    }
+
+    // Checks the ranges the firmware accepts; `encode` itself never fails.
+    pub fn validate(&self) -> Result<(), BledomError> {
+        let invalid = |message: String| Err(BledomError::InvalidParameter(message));
+        match *self {
+            Command::SetBrightness(value) if value > 100 => invalid(format!(
+                "brightness value {value} out of supported range (0-100)."
+            )),
+            Command::SetEffectSpeed(value) if value > 100 => invalid(format!(
+                "effect speed value {value} out of supported range (0-100)."
+            )),
+            Command::SetMusicSensitivity(value) if value > 100 => invalid(format!(
+                "music sensitivity value {value} out of supported range (0-100)."
+            )),
+            Command::SetColorWithMode { mode, .. } if mode == COLOR_MODE_RGB || mode == 0x01 => {
+                invalid(format!(
+                    "color mode byte {mode:#04x} is reserved for SetColor/SetWhite."
+                ))
+            }
+            Command::SetTime {
+                hour,
+                minute,
+                second,
+                day_of_week,
+            } => {
+                check_time(hour, minute)?;
+                if second > 59 {
+                    return invalid(format!(
+                        "second value {second} out of supported range (0-59)."
+                    ));
+                }
+                if !(1..=7).contains(&day_of_week) {
+                    return invalid(format!(
+                        "day of week value {day_of_week} out of supported range (1-7, 1=Monday)."
+                    ));
+                }
+                Ok(())
+            }
+            Command::SetSchedule { schedule, .. } => {
+                // Days are bit flags, valid range 0x00-0x7F (all bits 0-6 set for Monday-Sunday)
+                if !WEEK_DAYS.contains(schedule.days) {
+                    return invalid(format!(
+                        "days bitmask {:#02x} is invalid (max 0x7F).",
+                        schedule.days
+                    ));
+                }
+                check_time(schedule.hour, schedule.minute)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn check_time(hour: u8, minute: u8) -> Result<(), BledomError> {
+    if hour > 23 {
+        return Err(BledomError::InvalidParameter(format!(
+            "hour value {hour} out of supported range (0-23)."
+        )));
+    }
+    if minute > 59 {
+        return Err(BledomError::InvalidParameter(format!(
+            "minute value {minute} out of supported range (0-59)."
+        )));
+    }
+    Ok(())
+}
+
+// Values are decoded as sent; e.g. a brightness above 100 still decodes.
+impl TryFrom<&[u8]> for Command {
+    type Error = BledomError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let unrecognized =
+            || BledomError::InvalidParameter(format!("unrecognized command packet {data:02x?}."));
+        let &[PACKET_START, a, b, c, d, e, f, g, PACKET_END] = data else {
+            return Err(unrecognized());
+        };
+        let command = match [a, b, c, d, e, f, g] {
+            [0x00, 0x04, 0xf0, 0x00, 0x01, 0xff, 0x00] => Command::PowerOn,
+            [0x00, 0x04, 0x00, 0x00, 0x00, 0xff, 0x00] => Command::PowerOff,
+            [0x00, 0x05, COLOR_MODE_RGB, r, g, b, 0x00] => Command::SetColor(r, g, b),
+            [0x00, 0x05, 0x01, value, 0x00, 0x00, 0x00] => Command::SetWhite(value),
+            [0x00, 0x05, mode, red, green, blue, 0x00] => Command::SetColorWithMode {
+                mode,
+                red,
+                green,
+                blue,
+            },
+            [0x00, 0x01, value, 0x00, 0x00, 0x00, 0x00] => Command::SetBrightness(value),
+            [0x00, 0x03, value, 0x03, 0x00, 0x00, 0x00] => Command::SetEffect(value),
+            [0x00, 0x02, value, 0x00, 0x00, 0x00, 0x00] => Command::SetEffectSpeed(value),
+            [0x00, 0x83, hour, minute, second, day_of_week, 0x00] => Command::SetTime {
+                hour,
+                minute,
+                second,
+                day_of_week,
+            },
+            [0x00, 0x82, hour, minute, 0x00, kind @ (0x00 | 0x01), value] => Command::SetSchedule {
+                kind: if kind == 0x00 {
+                    ScheduleKind::On
+                } else {
+                    ScheduleKind::Off
+                },
+                schedule: Schedule {
+                    days: value & 0x7f,
+                    hour,
+                    minute,
+                    enabled: value & 0x80 != 0,
+                },
+            },
+            [0x04, 0x07, on @ (0x00 | 0x01), 0xff, 0xff, 0xff, 0x00] => Command::EnableMic(on == 1),
+            [0x05, 0x03, mode, 0x04, 0xff, 0xff, 0x00] => Command::SetMusicMode(mode),
+            [0x04, 0x06, value, 0xff, 0xff, 0xff, 0x00] => Command::SetMusicSensitivity(value),
+            _ => return Err(unrecognized()),
+        };
+        Ok(command)
+    }
+}
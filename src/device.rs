@@ -1,15 +1,35 @@
+use crate::colors::{kelvin_to_rgb, named_color, COOL_WHITE, DAYLIGHT_WHITE, WARM_WHITE};
+use crate::command::{Command, COLOR_MODE_RGB};
+use crate::guard::PowerOffGuard;
+use crate::sink::{BleSink, CommandSink, Delivery};
 use btleplug::api::bleuuid::uuid_from_u16;
-use btleplug::api::Characteristic;
-use btleplug::api::{Central, Manager as _, Peripheral as _, ScanFilter, WriteType};
+use btleplug::api::{BDAddr, CharPropFlags, Characteristic};
+use btleplug::api::{
+    Central, CentralEvent, CentralState, Manager as _, Peripheral as _, ScanFilter, WriteType,
+};
 use btleplug::platform::{Adapter, Manager, Peripheral};
-use log::{debug, error, info, warn};
-use std::time::Duration;
+use futures::{Stream, StreamExt};
+use log::{debug, error, info, trace, warn};
+use std::collections::HashSet;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use thiserror::Error;
+use tokio::sync::{Semaphore, SemaphorePermit};
 use tokio::time;
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
-const LIGHT_CHARACTERISTIC_UUID: Uuid = uuid_from_u16(0xFFF3);
-const CMD_DELAY: Duration = Duration::from_millis(100);
+pub const LIGHT_SERVICE_UUID: Uuid = uuid_from_u16(0xFFF0);
+pub const LIGHT_CHARACTERISTIC_UUID: Uuid = uuid_from_u16(0xFFF3);
+pub const NOTIFY_CHARACTERISTIC_UUID: Uuid = uuid_from_u16(0xFFF4);
+// Standard Battery Level characteristic (Battery Service 0x180F).
+pub const BATTERY_LEVEL_CHARACTERISTIC_UUID: Uuid = uuid_from_u16(0x2A19);
+pub const PACKET_START: u8 = 0x7e;
+pub const PACKET_END: u8 = 0xef;
+pub(crate) const CMD_DELAY: Duration = Duration::from_millis(100);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+const DEVICE_NAME: &str = "ELK-BLEDOM";
 
 #[derive(Debug, Error)]
 pub enum BledomError {
@@ -17,26 +37,247 @@ pub enum BledomError {
     BluetoothManagerError(#[from] btleplug::Error),
     #[error("No Bluetooth adapters found")]
     NoAdaptersFound,
-    #[error("Failed to start BLE scan: {0}")]
-    ScanError(String),
+    #[error("Bluetooth adapter is powered off or unavailable: {0}")]
+    AdapterUnavailable(String),
+    #[error("Requested Bluetooth adapter not found: {0}")]
+    AdapterNotFound(String),
+    // `source` keeps the btleplug error for matching on the root cause; `message` already
+    // includes its text.
+    #[error("Failed to start BLE scan: {message}")]
+    ScanError {
+        message: String,
+        #[source]
+        source: Option<btleplug::Error>,
+    },
     #[error("Could not find device after multiple tries")]
     DeviceNotFound,
-    #[error("Failed to connect to device after multiple tries: {0}")]
-    ConnectionFailed(String),
+    #[error("Failed to connect to device after multiple tries: {message}")]
+    ConnectionFailed {
+        message: String,
+        #[source]
+        source: Option<btleplug::Error>,
+    },
     #[error("Failed to discover services: {0}")]
     ServiceDiscoveryError(String),
-    #[error("Light characteristic (UUID: {LIGHT_CHARACTERISTIC_UUID}) not found on device")]
+    #[error(
+        "Light characteristic not found on device (default UUID: {LIGHT_CHARACTERISTIC_UUID})"
+    )]
     CharacteristicNotFound,
+    #[error("Light characteristic supports neither write nor write-without-response")]
+    CharacteristicNotWritable,
+    #[error("No notify characteristic found on device")]
+    NotifyCharacteristicNotFound,
+    #[error("Write did not complete within {0:?}")]
+    WriteTimeout(Duration),
+    #[error("Device is not connected")]
+    NotConnected,
+    #[error("Failed to disconnect from device: {0}")]
+    DisconnectFailed(String),
+    #[error("Properties not available for peripheral {0}")]
+    PropertiesUnavailable(BDAddr),
     #[error("Invalid parameter: {0}")]
     InvalidParameter(String),
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
     #[error("Other error: {0}")]
     Other(#[from] Box<dyn std::error::Error>),
 }
 
+// The btleplug and boxed errors aren't comparable themselves, so those variants compare equal
+// when their messages do.
+impl PartialEq for BledomError {
+    fn eq(&self, other: &Self) -> bool {
+        use BledomError::*;
+        match (self, other) {
+            (BluetoothManagerError(a), BluetoothManagerError(b)) => a.to_string() == b.to_string(),
+            (Other(a), Other(b)) => a.to_string() == b.to_string(),
+            (AdapterUnavailable(a), AdapterUnavailable(b))
+            | (AdapterNotFound(a), AdapterNotFound(b))
+            | (ServiceDiscoveryError(a), ServiceDiscoveryError(b))
+            | (DisconnectFailed(a), DisconnectFailed(b))
+            | (InvalidParameter(a), InvalidParameter(b))
+            | (InvalidConfig(a), InvalidConfig(b)) => a == b,
+            (
+                ScanError { message, source },
+                ScanError {
+                    message: other_message,
+                    source: other_source,
+                },
+            )
+            | (
+                ConnectionFailed { message, source },
+                ConnectionFailed {
+                    message: other_message,
+                    source: other_source,
+                },
+            ) => {
+                message == other_message
+                    && source.as_ref().map(ToString::to_string)
+                        == other_source.as_ref().map(ToString::to_string)
+            }
+            (WriteTimeout(a), WriteTimeout(b)) => a == b,
+            (PropertiesUnavailable(a), PropertiesUnavailable(b)) => a == b,
+            (NoAdaptersFound, NoAdaptersFound)
+            | (DeviceNotFound, DeviceNotFound)
+            | (CharacteristicNotFound, CharacteristicNotFound)
+            | (CharacteristicNotWritable, CharacteristicNotWritable)
+            | (NotifyCharacteristicNotFound, NotifyCharacteristicNotFound)
+            | (NotConnected, NotConnected) => true,
+            _ => false,
+        }
+    }
+}
+
+impl BledomError {
+    fn scan(context: Option<&str>, source: btleplug::Error) -> Self {
+        let message = match context {
+            Some(context) => format!("{context}: {source}"),
+            None => source.to_string(),
+        };
+        BledomError::ScanError {
+            message,
+            source: Some(source),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DiscoveredDevice {
+    pub address: BDAddr,
+    pub rssi: Option<i16>,
+    pub name: String,
+}
+
+pub struct BledomDevice<S = BleSink> {
+    sink: S,
+    // Last power state/color/brightness written, decoded from outgoing packets; see
+    // `current_color`. Shared with the keepalive task, which picks its ping from it.
+    tracked: Arc<Mutex<TrackedState>>,
+    command_delay: Duration,
+    command_retries: u8,
+    gamma: f32,
+    color_mode: u8,
+    // Shared with the keepalive task, so pings are reported like every other packet.
+    on_command: Option<SharedCommandCallback>,
+    rate_limiter: Option<Arc<Semaphore>>,
+    coalescer: Option<Arc<Coalescer>>,
+    keepalive: Option<Keepalive>,
+}
+
+// Stops the keepalive task when the device is dropped (or on `stop_keepalive`/`disconnect`).
 #[derive(Debug)]
-pub struct BledomDevice {
-    peripheral: Peripheral,
-    characteristic: Characteristic,
+struct Keepalive {
+    stop: CancellationToken,
+    interval: Duration,
+}
+
+impl Drop for Keepalive {
+    fn drop(&mut self) {
+        self.stop.cancel();
+    }
+}
+
+// Queues writes in order; see `coalesce_updates`. Color and brightness updates park in a slot
+// before queueing, and whichever call of that kind gets the queue next sends the newest one.
+#[derive(Debug, Default)]
+struct Coalescer {
+    queue: tokio::sync::Mutex<()>,
+    pending_color: Mutex<Option<Command>>,
+    pending_brightness: Mutex<Option<Command>>,
+}
+
+impl Coalescer {
+    fn slot(&self, command: &Command) -> Option<&Mutex<Option<Command>>> {
+        match command {
+            Command::SetColor(..) | Command::SetColorWithMode { .. } => Some(&self.pending_color),
+            Command::SetBrightness(_) => Some(&self.pending_brightness),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TrackedColor {
+    // As written on the wire, i.e. gamma-corrected; what `flash`/`stop_effect` reapply.
+    wire: (u8, u8, u8),
+    // As passed in by the caller; see `current_color`.
+    requested: (u8, u8, u8),
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct TrackedState {
+    // Last power command sent; best-effort, it drifts if the physical remote is used.
+    powered: bool,
+    color: Option<TrackedColor>,
+    // The last color command built, so `record` can tell what the caller asked for once the
+    // packet goes out.
+    pending_color: Option<TrackedColor>,
+    brightness: Option<u8>,
+    mode: Option<LightMode>,
+    last_command: Option<[u8; 9]>,
+    last_write: Option<time::Instant>,
+}
+
+impl TrackedState {
+    // Updates the state from a packet that was just written, returning it decoded if it is a
+    // known command.
+    fn record(&mut self, data: &[u8]) -> Option<Command> {
+        self.last_write = Some(time::Instant::now());
+        if let Ok(packet) = data.try_into() {
+            self.last_command = Some(packet);
+        }
+        let command = Command::try_from(data).ok()?;
+        match command {
+            Command::PowerOn => self.powered = true,
+            // Whatever ran before is gone as far as `leave_effect` is concerned, so a color set
+            // while off doesn't turn the strip back on.
+            Command::PowerOff => {
+                self.powered = false;
+                self.mode = None;
+            }
+            Command::SetColor(red, green, blue)
+            | Command::SetColorWithMode {
+                red, green, blue, ..
+            } => {
+                self.record_color((red, green, blue));
+                self.mode = Some(LightMode::Static);
+            }
+            Command::SetWhite(_) => self.mode = Some(LightMode::Static),
+            Command::SetEffect(_) | Command::SetMusicMode(_) => self.mode = Some(LightMode::Effect),
+            Command::SetBrightness(value) => self.brightness = Some(value),
+            _ => {}
+        }
+        Some(command)
+    }
+
+    fn record_color(&mut self, wire: (u8, u8, u8)) {
+        self.color = match (self.pending_color, self.color) {
+            (Some(pending), _) if pending.wire == wire => Some(pending),
+            // E.g. a raw packet repeating the current color.
+            (_, Some(current)) if current.wire == wire => Some(current),
+            _ => Some(TrackedColor {
+                wire,
+                requested: wire,
+            }),
+        };
+    }
+}
+
+impl<S: std::fmt::Debug> std::fmt::Debug for BledomDevice<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BledomDevice")
+            .field("sink", &self.sink)
+            .field("tracked", &self.tracked)
+            .field("command_delay", &self.command_delay)
+            .field("command_retries", &self.command_retries)
+            .field("gamma", &self.gamma)
+            .field("color_mode", &self.color_mode)
+            .field("on_command", &self.on_command.is_some())
+            .field("rate_limiter", &self.rate_limiter)
+            .field("coalescer", &self.coalescer)
+            .field("keepalive", &self.keepalive.is_some())
+            .finish()
+    }
 }
 
 pub struct Days {
@@ -67,6 +308,114 @@ pub const WEEK_DAYS: Days = Days {
     none: 0x00,
 };
 
+impl Days {
+    // True if every bit of `flag` is a known day bit.
+    pub fn contains(&self, flag: u8) -> bool {
+        flag & !self.all == 0
+    }
+
+    #[cfg(feature = "time")]
+    pub fn from_flags(days: &[chrono::Weekday]) -> u8 {
+        days.iter().fold(WEEK_DAYS.none, |mask, day| {
+            mask | 1 << day.num_days_from_monday()
+        })
+    }
+}
+
+// Whether the strip was last told to show a fixed color or to run an effect (including the
+// music modes), as far as this handle knows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightMode {
+    Static,
+    Effect,
+}
+
+// Microphone equalizer modes. The codes come from community captures of the vendor app and
+// not every clone has a microphone; `Raw` passes any other mode byte through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MusicMode {
+    Classic,
+    Soft,
+    Dynamic,
+    Disco,
+    Raw(u8),
+}
+
+impl MusicMode {
+    pub fn code(&self) -> u8 {
+        match *self {
+            MusicMode::Classic => 0x80,
+            MusicMode::Soft => 0x81,
+            MusicMode::Dynamic => 0x82,
+            MusicMode::Disco => 0x83,
+            MusicMode::Raw(code) => code,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleKind {
+    On,
+    Off,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Schedule {
+    pub days: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub enabled: bool,
+}
+
+// A 0-100 value for brightness and effect speed, so out-of-range values are caught when the
+// value is built rather than at send time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Percent(u8);
+
+impl Percent {
+    pub fn new(value: u8) -> Result<Percent, BledomError> {
+        if value > 100 {
+            return Err(BledomError::InvalidParameter(format!(
+                "percentage {value} out of range (0-100)."
+            )));
+        }
+        Ok(Percent(value))
+    }
+
+    pub const fn new_clamped(value: u8) -> Percent {
+        Percent(if value > 100 { 100 } else { value })
+    }
+
+    pub const fn get(self) -> u8 {
+        self.0
+    }
+}
+
+impl From<Percent> for u8 {
+    fn from(percent: Percent) -> u8 {
+        percent.0
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LightState {
+    pub power: Option<bool>,
+    pub rgb: Option<(u8, u8, u8)>,
+    pub brightness: Option<u8>,
+    pub effect: Option<Effect>,
+    pub effect_speed: Option<u8>,
+}
+
+// An animated counterpart to `LightState`, e.g. for saved scenes; see `apply_effect_scene`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EffectScene {
+    pub effect: Effect,
+    pub speed: Percent,
+    pub brightness: Percent,
+}
+
 pub struct Effects {
     pub jump_red_green_blue: u8,
     pub jump_red_green_blue_yellow_cyan_magenta_white: u8,
@@ -117,12 +466,220 @@ pub const EFFECTS: Effects = Effects {
     blink_red_green_blue_yellow_cyan_magenta_white: 0x95,
 };
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Effect {
+    JumpRgb,
+    JumpRgbycmw,
+    CrossfadeRed,
+    CrossfadeGreen,
+    CrossfadeBlue,
+    CrossfadeYellow,
+    CrossfadeCyan,
+    CrossfadeMagenta,
+    CrossfadeWhite,
+    CrossfadeRedGreen,
+    CrossfadeRedBlue,
+    CrossfadeGreenBlue,
+    CrossfadeRgb,
+    CrossfadeRgbycmw,
+    BlinkRed,
+    BlinkGreen,
+    BlinkBlue,
+    BlinkYellow,
+    BlinkCyan,
+    BlinkMagenta,
+    BlinkWhite,
+    BlinkRgbycmw,
+}
+
+impl Effect {
+    pub fn all() -> &'static [Effect] {
+        &[
+            Effect::JumpRgb,
+            Effect::JumpRgbycmw,
+            Effect::CrossfadeRed,
+            Effect::CrossfadeGreen,
+            Effect::CrossfadeBlue,
+            Effect::CrossfadeYellow,
+            Effect::CrossfadeCyan,
+            Effect::CrossfadeMagenta,
+            Effect::CrossfadeWhite,
+            Effect::CrossfadeRedGreen,
+            Effect::CrossfadeRedBlue,
+            Effect::CrossfadeGreenBlue,
+            Effect::CrossfadeRgb,
+            Effect::CrossfadeRgbycmw,
+            Effect::BlinkRed,
+            Effect::BlinkGreen,
+            Effect::BlinkBlue,
+            Effect::BlinkYellow,
+            Effect::BlinkCyan,
+            Effect::BlinkMagenta,
+            Effect::BlinkWhite,
+            Effect::BlinkRgbycmw,
+        ]
+    }
+
+    pub fn code(&self) -> u8 {
+        match self {
+            Effect::JumpRgb => EFFECTS.jump_red_green_blue,
+            Effect::JumpRgbycmw => EFFECTS.jump_red_green_blue_yellow_cyan_magenta_white,
+            Effect::CrossfadeRed => EFFECTS.crossfade_red,
+            Effect::CrossfadeGreen => EFFECTS.crossfade_green,
+            Effect::CrossfadeBlue => EFFECTS.crossfade_blue,
+            Effect::CrossfadeYellow => EFFECTS.crossfade_yellow,
+            Effect::CrossfadeCyan => EFFECTS.crossfade_cyan,
+            Effect::CrossfadeMagenta => EFFECTS.crossfade_magenta,
+            Effect::CrossfadeWhite => EFFECTS.crossfade_white,
+            Effect::CrossfadeRedGreen => EFFECTS.crossfade_red_green,
+            Effect::CrossfadeRedBlue => EFFECTS.crossfade_red_blue,
+            Effect::CrossfadeGreenBlue => EFFECTS.crossfade_green_blue,
+            Effect::CrossfadeRgb => EFFECTS.crossfade_red_green_blue,
+            Effect::CrossfadeRgbycmw => EFFECTS.crossfade_red_green_blue_yellow_cyan_magenta_white,
+            Effect::BlinkRed => EFFECTS.blink_red,
+            Effect::BlinkGreen => EFFECTS.blink_green,
+            Effect::BlinkBlue => EFFECTS.blink_blue,
+            Effect::BlinkYellow => EFFECTS.blink_yellow,
+            Effect::BlinkCyan => EFFECTS.blink_cyan,
+            Effect::BlinkMagenta => EFFECTS.blink_magenta,
+            Effect::BlinkWhite => EFFECTS.blink_white,
+            Effect::BlinkRgbycmw => EFFECTS.blink_red_green_blue_yellow_cyan_magenta_white,
+        }
+    }
+}
+
+// For effect codes received from elsewhere (e.g. a web UI); unknown codes are rejected
+// before anything is sent.
+impl TryFrom<u8> for Effect {
+    type Error = BledomError;
+
+    fn try_from(code: u8) -> Result<Self, Self::Error> {
+        Effect::all()
+            .iter()
+            .find(|effect| effect.code() == code)
+            .copied()
+            .ok_or_else(|| {
+                BledomError::InvalidParameter(format!("unknown effect code {code:#04x}."))
+            })
+    }
+}
+
+impl std::fmt::Display for Effect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Effect::JumpRgb => "jump_red_green_blue",
+            Effect::JumpRgbycmw => "jump_red_green_blue_yellow_cyan_magenta_white",
+            Effect::CrossfadeRed => "crossfade_red",
+            Effect::CrossfadeGreen => "crossfade_green",
+            Effect::CrossfadeBlue => "crossfade_blue",
+            Effect::CrossfadeYellow => "crossfade_yellow",
+            Effect::CrossfadeCyan => "crossfade_cyan",
+            Effect::CrossfadeMagenta => "crossfade_magenta",
+            Effect::CrossfadeWhite => "crossfade_white",
+            Effect::CrossfadeRedGreen => "crossfade_red_green",
+            Effect::CrossfadeRedBlue => "crossfade_red_blue",
+            Effect::CrossfadeGreenBlue => "crossfade_green_blue",
+            Effect::CrossfadeRgb => "crossfade_red_green_blue",
+            Effect::CrossfadeRgbycmw => "crossfade_red_green_blue_yellow_cyan_magenta_white",
+            Effect::BlinkRed => "blink_red",
+            Effect::BlinkGreen => "blink_green",
+            Effect::BlinkBlue => "blink_blue",
+            Effect::BlinkYellow => "blink_yellow",
+            Effect::BlinkCyan => "blink_cyan",
+            Effect::BlinkMagenta => "blink_magenta",
+            Effect::BlinkWhite => "blink_white",
+            Effect::BlinkRgbycmw => "blink_red_green_blue_yellow_cyan_magenta_white",
+        };
+        f.write_str(name)
+    }
+}
+
+pub type NameFilter = Box<dyn Fn(&str) -> bool + Send + Sync>;
+pub type CommandCallback = Box<dyn Fn(&[u8]) + Send + Sync>;
+type SharedCommandCallback = Arc<dyn Fn(&[u8]) + Send + Sync>;
+pub type ProgressCallback = Box<dyn Fn(Progress) + Send + Sync>;
+
+// Stages `build()` goes through, reported to `BledomDeviceBuilder::on_progress`. Attempts
+// count from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    Scanning { attempt: u32 },
+    Found,
+    Connecting { attempt: u32 },
+    Connected,
+    DiscoveringServices,
+}
+
+// Effects are (de)serialized by their `Display` name, e.g. "blink_red", like the CLI uses.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Effect {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Effect {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Effect::all()
+            .iter()
+            .find(|e| e.to_string() == name)
+            .copied()
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown effect {name:?}")))
+    }
+}
+
+// Plain numbers on the wire; values above 100 are rejected when deserializing.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Percent {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Percent {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = u8::deserialize(deserializer)?;
+        Percent::new(value).map_err(serde::de::Error::custom)
+    }
+}
+
 #[derive(Default)]
 pub struct BledomDeviceBuilder {
     scan_retries: Option<u8>,
     scan_interval_ms: Option<u64>,
     connection_retries: Option<u8>,
     connection_interval_ms: Option<u64>,
+    address: Option<BDAddr>,
+    command_delay_ms: Option<u64>,
+    adapter_index: Option<usize>,
+    adapter_name: Option<String>,
+    command_retries: Option<u8>,
+    gamma: Option<f32>,
+    color_mode: Option<u8>,
+    name_filter: Option<NameFilter>,
+    auto_reconnect: bool,
+    skip_scan: bool,
+    on_command: Option<CommandCallback>,
+    rate_limiter: Option<Arc<Semaphore>>,
+    coalesce_updates: bool,
+    keepalive: Option<Duration>,
+    scan_timeout: Option<Duration>,
+    connection_timeout: Option<Duration>,
+    #[cfg(feature = "time")]
+    handshake: bool,
+    write_type: Option<WriteType>,
+    scan_filter_services: Option<Vec<Uuid>>,
+    peripheral: Option<Peripheral>,
+    on_progress: Option<ProgressCallback>,
+    characteristic_uuid: Option<Uuid>,
+    dry_run: bool,
+    adapter: Option<Adapter>,
+    log_discovered: bool,
+    write_timeout: Option<Duration>,
+    connection_backoff: bool,
 }
 
 impl BledomDeviceBuilder {
@@ -130,6 +687,7 @@ impl BledomDeviceBuilder {
         Self::default()
     }
 
+    // At least 1: the retry count bounds the scan attempts, so 0 would never scan.
     pub fn scan_retries(mut self, retries: u8) -> Self {
         self.scan_retries = Some(retries);
         self
@@ -150,88 +708,474 @@ impl BledomDeviceBuilder {
         self
     }
 
-    pub async fn build(self) -> Result<BledomDevice, BledomError> {
-        let scan_retries = self.scan_retries.unwrap_or(10);
-        let scan_interval_ms = self.scan_interval_ms.unwrap_or(1000);
-        let connection_retries = self.connection_retries.unwrap_or(10);
-        let connection_interval_ms = self.connection_interval_ms.unwrap_or(100);
+    // Must be non-zero. Scanning stops at whichever of this and `scan_retries` is hit first.
+    pub fn scan_timeout(mut self, timeout: Duration) -> Self {
+        self.scan_timeout = Some(timeout);
+        self
+    }
+
+    // Doubles the connection interval after each failed attempt (capped at 5s, with jitter)
+    // instead of retrying at a fixed cadence; helps on congested radios.
+    pub fn connection_backoff(mut self, enabled: bool) -> Self {
+        self.connection_backoff = enabled;
+        self
+    }
+
+    pub fn connection_timeout(mut self, timeout: Duration) -> Self {
+        self.connection_timeout = Some(timeout);
+        self
+    }
+
+    pub fn address(mut self, address: BDAddr) -> Self {
+        self.address = Some(address);
+        self
+    }
+
+    pub fn command_delay_ms(mut self, delay: u64) -> Self {
+        self.command_delay_ms = Some(delay);
+        self
+    }
+
+    pub fn adapter_index(mut self, index: usize) -> Self {
+        self.adapter_index = Some(index);
+        self
+    }
+
+    // Matched as a substring of the platform-specific adapter info, e.g. "hci1".
+    pub fn adapter_name(mut self, name: String) -> Self {
+        self.adapter_name = Some(name);
+        self
+    }
+
+    pub fn command_retries(mut self, retries: u8) -> Self {
+        self.command_retries = Some(retries);
+        self
+    }
+
+    // 1.0 leaves colors untouched; around 2.2 makes dim fades look perceptually linear. Must be
+    // a positive, finite number.
+    pub fn gamma(mut self, gamma: f32) -> Self {
+        self.gamma = Some(gamma);
+        self
+    }
+
+    // Byte 3 of the color command, `COLOR_MODE_RGB` (0x03) by default. Some strips only react
+    // to colors sent with 0x00 there. 0x01 is rejected, since it selects the white channel.
+    pub fn color_mode_byte(mut self, mode: u8) -> Self {
+        self.color_mode = Some(mode);
+        self
+    }
+
+    pub fn name_filter(mut self, filter: NameFilter) -> Self {
+        self.name_filter = Some(filter);
+        self
+    }
+
+    pub fn auto_reconnect(mut self, enabled: bool) -> Self {
+        self.auto_reconnect = enabled;
+        self
+    }
+
+    // Called with every packet right before it is written, e.g. to dump it for comparison
+    // with captures from the official app.
+    pub fn on_command(mut self, callback: CommandCallback) -> Self {
+        self.on_command = Some(callback);
+        self
+    }
+
+    // Some strips drop the first write after connecting; this sends a throwaway time sync
+    // before `build()` returns so the first real command lands. Needs the `time` feature: the
+    // sync has to carry the local time, or the strip's on/off timers would fire at the wrong
+    // hour.
+    #[cfg(feature = "time")]
+    pub fn handshake(mut self, enabled: bool) -> Self {
+        self.handshake = enabled;
+        self
+    }
+
+    // Write type used unless a call overrides it (`write_raw_with`, `power_off_with`, ...).
+    // Skips detecting it from the characteristic's properties, which prefers
+    // `WithoutResponse` for throughput.
+    pub fn default_write_type(mut self, write_type: WriteType) -> Self {
+        self.write_type = Some(write_type);
+        self
+    }
+
+    // Scans and connects through an adapter the caller already owns instead of creating a
+    // new `Manager`; `adapter_index`/`adapter_name` are then ignored.
+    pub fn with_adapter(mut self, adapter: Adapter) -> Self {
+        self.adapter = Some(adapter);
+        self
+    }
+
+    // Shares write pacing with every other device built with the same semaphore: a write and
+    // its command delay hold one permit, so `Semaphore::new(1)` keeps strips on one radio from
+    // flooding it.
+    pub fn rate_limiter(mut self, limiter: Arc<Semaphore>) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
 
-        debug!("newing device...");
-        let manager = Manager::new().await?;
-        let central = get_central(&manager).await?;
+    // Queues every write (commands, batches and raw packets) instead of letting concurrent
+    // calls race for the radio. Color and brightness updates are collapsed: a call that gets
+    // its turn sends the newest value of its kind that is waiting, and a call whose value was
+    // already sent that way returns `Ok` without writing. The newest value is sent even if
+    // the call that set it was cancelled meanwhile. Helps UIs like sliders that fire far more
+    // updates than the strip can take. Off by default, so every command is sent.
+    pub fn coalesce_updates(mut self, enabled: bool) -> Self {
+        self.coalesce_updates = enabled;
+        self
+    }
+
+    // Re-sends the last brightness whenever no command was written for `interval`, for strips
+    // that drop an idle connection (often after about 30s). Before any brightness was set it
+    // re-sends power-on instead, once the strip was switched on through this handle and isn't
+    // running an effect (which that could restart); until then nothing is sent. The task ends
+    // when the device is dropped, disconnected or `stop_keepalive` is called. `interval` must
+    // be non-zero.
+    pub fn keepalive(mut self, interval: Duration) -> Self {
+        self.keepalive = Some(interval);
+        self
+    }
+
+    // Upper bound for a single BLE write, after which it fails with `WriteTimeout` (and may be
+    // retried per `command_retries`). Defaults to 5 seconds; must be non-zero.
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = Some(timeout);
+        self
+    }
+
+    // Logs every peripheral seen while scanning (name, address, RSSI) at info level, once per
+    // address. Combine with `scan_filter_services(Vec::new())` to see non-matching devices too.
+    pub fn log_discovered(mut self, enabled: bool) -> Self {
+        self.log_discovered = enabled;
+        self
+    }
+
+    // Still scans and connects, but commands are only logged instead of written. To skip the
+    // hardware entirely, use `build_with_sink` with a mock sink.
+    pub fn dry_run(mut self, enabled: bool) -> Self {
+        self.dry_run = enabled;
+        self
+    }
+
+    // For firmware variants that take commands on another characteristic (e.g. 0xFFF1).
+    // Defaults to `LIGHT_CHARACTERISTIC_UUID`.
+    pub fn characteristic_uuid(mut self, uuid: Uuid) -> Self {
+        self.characteristic_uuid = Some(uuid);
+        self
+    }
+
+    pub fn on_progress(mut self, callback: ProgressCallback) -> Self {
+        self.on_progress = Some(callback);
+        self
+    }
+
+    fn report(&self, progress: Progress) {
+        if let Some(on_progress) = &self.on_progress {
+            on_progress(progress);
+        }
+    }
+
+    // Only surfaces peripherals advertising one of these services while scanning. Defaults to
+    // `LIGHT_SERVICE_UUID`; pass an empty list to scan for everything, e.g. for clones that
+    // don't advertise their service.
+    pub fn scan_filter_services(mut self, services: Vec<Uuid>) -> Self {
+        self.scan_filter_services = Some(services);
+        self
+    }
 
-        debug!("adapter in used:\n{:#?}", central);
+    async fn scan_for_light(
+        &self,
+        central: &Adapter,
+        policy: RetryPolicy,
+    ) -> Result<Peripheral, BledomError> {
         let mut light = None;
+        let filter = ScanFilter {
+            services: self
+                .scan_filter_services
+                .clone()
+                .unwrap_or_else(|| vec![LIGHT_SERVICE_UUID]),
+        };
 
-        central
-            .start_scan(ScanFilter::default())
-            .await
-            .map_err(|e| BledomError::ScanError(e.to_string()))?;
+        if let Err(e) = central.start_scan(filter).await {
+            return Err(match powered_off_adapter(central).await {
+                Some(info) => BledomError::AdapterUnavailable(info),
+                None => BledomError::scan(None, e),
+            });
+        }
+
+        // Adapter events let a strip that shows up mid-interval be picked immediately; without
+        // them this falls back to polling every interval.
+        let mut events = match central.events().await {
+            Ok(events) => Some(events),
+            Err(e) => {
+                debug!(device:% = self.log_id(); "adapter events unavailable, polling instead: {}", e);
+                None
+            }
+        };
 
+        let started = Instant::now();
         let mut find_count = 0;
+        let mut logged = HashSet::new();
+        let mut unresolved = None;
         while light.is_none() {
-            info!("trying to find light...");
-            if find_count >= scan_retries {
+            info!(device:% = self.log_id(), attempt = find_count + 1; "trying to find light...");
+            if policy.exhausted(find_count, started) {
                 central.stop_scan().await.ok(); // Attempt to stop scan on error
-                return Err(BledomError::DeviceNotFound);
+                return Err(not_found(unresolved));
             }
-            match find_light(&central).await {
-                Ok(p) => {
-                    light = Some(p);
+            self.report(Progress::Scanning {
+                attempt: find_count + 1,
+            });
+            if self.log_discovered {
+                log_new_peripherals(central, &mut logged).await;
+            }
+            match match_lights(central, self.address, self.name_filter.as_deref()).await {
+                Ok((found, _)) if !found.is_empty() => {
+                    if found.len() > 1 {
+                        info!(
+                            device:% = self.log_id();
+                            "found {} matching lights, using {}; set an address to pick another",
+                            found.len(),
+                            found[0].address()
+                        );
+                    }
+                    light = found.into_iter().next();
+                }
+                Ok((_, without_properties)) => {
+                    unresolved = without_properties;
+                    if let Some(info) = powered_off_adapter(central).await {
+                        central.stop_scan().await.ok();
+                        return Err(BledomError::AdapterUnavailable(info));
+                    }
                 }
-                Err(BledomError::DeviceNotFound) => {}
                 Err(e) => {
+                    let unavailable = powered_off_adapter(central).await;
                     central.stop_scan().await.ok();
-                    return Err(e);
+                    return Err(unavailable.map_or(e, BledomError::AdapterUnavailable));
                 }
             }
             find_count += 1;
-            time::sleep(Duration::from_millis(scan_interval_ms)).await;
+            if light.is_none() {
+                light = self
+                    .wait_for_light(central, events.as_mut(), policy.interval)
+                    .await;
+            }
         }
 
         central
             .stop_scan()
             .await
-            .map_err(|e| BledomError::ScanError(format!("failed to stop scan: {}", e)))?;
-
-        let lc = light.clone().ok_or(BledomError::DeviceNotFound)?;
-        let mut connect_count = 0;
-        let mut connect_status = false;
-        while !connect_status {
-            info!("trying to connect to light");
-            match lc.connect().await {
-                Ok(_) => {
-                    connect_status = true;
-                }
-                Err(e) => {
-                    warn!("failed to connect light: {}", e);
-                    connect_count += 1;
-                    if connect_count >= connection_retries {
-                        return Err(BledomError::ConnectionFailed(e.to_string()));
-                    } else {
-                        time::sleep(Duration::from_millis(connection_interval_ms)).await;
-                    }
+            .map_err(|e| BledomError::scan(Some("failed to stop scan"), e))?;
+
+        light.ok_or(BledomError::DeviceNotFound)
+    }
+
+    // Waits up to `interval` for a discovery event from a matching strip.
+    async fn wait_for_light(
+        &self,
+        central: &Adapter,
+        events: Option<&mut Pin<Box<dyn Stream<Item = CentralEvent> + Send>>>,
+        interval: Duration,
+    ) -> Option<Peripheral> {
+        let Some(events) = events else {
+            time::sleep(interval).await;
+            return None;
+        };
+        let wait = async {
+            while let Some(event) = events.next().await {
+                let (CentralEvent::DeviceDiscovered(id) | CentralEvent::DeviceUpdated(id)) = event
+                else {
+                    continue;
+                };
+                let Ok(p) = central.peripheral(&id).await else {
+                    continue;
+                };
+                let matched = is_light(&p, self.address, self.name_filter.as_deref()).await;
+                if matched == Match::Light {
+                    return Some(p);
                 }
             }
+            // The event stream closed; just sit out the rest of the interval.
+            std::future::pending().await
+        };
+        time::timeout(interval, wait).await.unwrap_or(None)
+    }
+
+    // Builds a device that writes through `sink` instead of a scanned BLE peripheral; only the
+    // command settings (delay, retries, gamma, color mode) of the builder apply.
+    pub fn build_with_sink<S: CommandSink>(self, sink: S) -> BledomDevice<S> {
+        BledomDevice {
+            sink,
+            tracked: Arc::new(Mutex::new(TrackedState::default())),
+            command_delay: self
+                .command_delay_ms
+                .map(Duration::from_millis)
+                .unwrap_or(CMD_DELAY),
+            command_retries: self.command_retries.unwrap_or(0),
+            gamma: self.gamma.unwrap_or(1.0),
+            color_mode: self.color_mode.unwrap_or(COLOR_MODE_RGB),
+            on_command: self.on_command.map(Arc::from),
+            rate_limiter: self.rate_limiter,
+            coalescer: self.coalesce_updates.then(Arc::default),
+            keepalive: None,
         }
+    }
 
-        lc.discover_services()
-            .await
-            .map_err(|e| BledomError::ServiceDiscoveryError(e.to_string()))?;
+    // Rejects settings under which `build()` would give up without ever scanning or every
+    // write would fail, before touching the adapter.
+    fn validate(&self) -> Result<(), BledomError> {
+        let invalid = |message: &str| Err(BledomError::InvalidConfig(message.to_string()));
+        if self.scan_retries == Some(0) {
+            return invalid("scan_retries must be at least 1");
+        }
+        if self.scan_timeout == Some(Duration::ZERO) {
+            return invalid("scan_timeout must be non-zero");
+        }
+        if self.write_timeout == Some(Duration::ZERO) {
+            return invalid("write_timeout must be non-zero");
+        }
+        if self.keepalive == Some(Duration::ZERO) {
+            return invalid("keepalive interval must be non-zero");
+        }
+        if let Some(mode) = self.color_mode.filter(|&mode| mode != COLOR_MODE_RGB) {
+            let command = Command::SetColorWithMode {
+                mode,
+                red: 0,
+                green: 0,
+                blue: 0,
+            };
+            if let Err(BledomError::InvalidParameter(message)) = command.validate() {
+                return invalid(&message);
+            }
+        }
+        if let Some(gamma) = self.gamma {
+            if !gamma.is_finite() || gamma <= 0.0 {
+                return invalid(&format!("gamma must be positive and finite, got {gamma}"));
+            }
+        }
+        Ok(())
+    }
 
-        let chars = lc.characteristics();
+    fn connection_policy(&self) -> RetryPolicy {
+        let mut policy = RetryPolicy::new(
+            self.connection_retries,
+            self.connection_timeout,
+            Duration::from_millis(self.connection_interval_ms.unwrap_or(100)),
+        );
+        policy.backoff = self.connection_backoff;
+        policy
+    }
 
-        let cmd_char = chars
-            .iter()
-            .find(|c| c.uuid == LIGHT_CHARACTERISTIC_UUID)
-            .ok_or(BledomError::CharacteristicNotFound)?;
+    // The `device` key on records logged before a strip is picked: the requested address, if
+    // any.
+    fn log_id(&self) -> String {
+        self.address
+            .map_or_else(|| "-".to_string(), |address| address.to_string())
+    }
+
+    pub async fn build(mut self) -> Result<BledomDevice, BledomError> {
+        self.validate()?;
+        let scan_policy = RetryPolicy::new(
+            self.scan_retries,
+            self.scan_timeout,
+            Duration::from_millis(self.scan_interval_ms.unwrap_or(1000)),
+        );
+        let connection_policy = self.connection_policy();
+
+        debug!(device:% = self.log_id(); "newing device...");
+        let (peripheral, adapter) = match self.peripheral.take() {
+            Some(peripheral) => (peripheral, None),
+            None => {
+                let central = match self.adapter.take() {
+                    Some(adapter) => adapter,
+                    None => {
+                        let manager = Manager::new().await?;
+                        get_central(&manager, self.adapter_index, self.adapter_name.as_deref())
+                            .await?
+                    }
+                };
+
+                debug!(device:% = self.log_id(); "adapter in used:\n{:#?}", central);
+                let peripheral = match (self.skip_scan, self.address) {
+                    (true, Some(address)) => find_known_light(&central, address).await?,
+                    _ => self.scan_for_light(&central, scan_policy).await?,
+                };
+                self.report(Progress::Found);
+                connect_light(&peripheral, connection_policy, self.on_progress.as_deref()).await?;
+                self.report(Progress::Connected);
+                (peripheral, Some(central))
+            }
+        };
+        self.finish(peripheral, adapter, connection_policy).await
+    }
 
-        let peripheral = light.unwrap();
+    // Connects to a peripheral the caller picked from their own scan; see `connect`.
+    async fn connect_peripheral(
+        mut self,
+        peripheral: Peripheral,
+    ) -> Result<BledomDevice, BledomError> {
+        self.validate()?;
+        let connection_policy = self.connection_policy();
+        connect_light(&peripheral, connection_policy, self.on_progress.as_deref()).await?;
+        self.report(Progress::Connected);
+        let adapter = self.adapter.take();
+        self.finish(peripheral, adapter, connection_policy).await
+    }
+
+    // Service discovery and setup on an already connected peripheral.
+    async fn finish(
+        self,
+        peripheral: Peripheral,
+        adapter: Option<Adapter>,
+        connection_policy: RetryPolicy,
+    ) -> Result<BledomDevice, BledomError> {
+        self.report(Progress::DiscoveringServices);
+        let characteristic = discover_characteristic(
+            &peripheral,
+            self.characteristic_uuid
+                .unwrap_or(LIGHT_CHARACTERISTIC_UUID),
+        )
+        .await?;
+        let address = peripheral.address();
+        let write_type = match self.write_type {
+            Some(write_type) => write_type,
+            None => detect_write_type(&characteristic, address)?,
+        };
+        debug!(device:% = address; "using write type {:?}", write_type);
+
+        let name = match peripheral.properties().await {
+            Ok(properties) => properties.and_then(|p| p.local_name),
+            Err(_) => None,
+        };
+        info!(device:% = address; "connected to {:?} ({})", name, address);
 
-        let device = BledomDevice {
+        let sink = BleSink {
             peripheral,
-            characteristic: cmd_char.to_owned(),
+            characteristic,
+            write_type,
+            connection_policy,
+            auto_reconnect: self.auto_reconnect,
+            name,
+            adapter,
+            dry_run: self.dry_run,
+            write_timeout: self.write_timeout.unwrap_or(WRITE_TIMEOUT),
         };
+        #[cfg(feature = "time")]
+        let handshake = self.handshake;
+        let keepalive = self.keepalive.filter(|_| !sink.dry_run);
+        let mut device = self.build_with_sink(sink);
+        if let Some(interval) = keepalive {
+            device.start_keepalive(interval)?;
+        }
+        #[cfg(feature = "time")]
+        if handshake {
+            debug!(device:% = address; "sending handshake...");
+            if let Err(e) = device.sync_time().await {
+                warn!(device:% = address; "handshake failed: {}", e);
+            }
+        }
         Ok(device)
     }
 }
@@ -241,55 +1185,405 @@ impl BledomDevice {
         BledomDeviceBuilder::new()
     }
 
-    async fn send_command_bytes(&self, data: &[u8]) -> Result<(), BledomError> {
-        if data.len() != 9 || data[0] != 0x7e || data[8] != 0xef {
-            return Err(BledomError::InvalidParameter("malformed command byte array (expected 9 bytes, starting with 0x7e and ending with 0xef)".to_string()));
-        }
-        self.peripheral
-            .write(&self.characteristic, data, WriteType::WithoutResponse)
-            .await?;
-        time::sleep(CMD_DELAY).await;
-        Ok(())
+    // Connects to a previously seen strip without scanning. This only works if the platform
+    // still knows the peripheral (e.g. BlueZ caches paired/seen devices); otherwise it fails
+    // with `DeviceNotFound` and a regular `build()` is needed.
+    pub async fn from_address(address: BDAddr) -> Result<BledomDevice, BledomError> {
+        let mut builder = BledomDeviceBuilder::new().address(address);
+        builder.skip_scan = true;
+        builder.build().await
     }
 
-    pub async fn power_on(&self) -> Result<(), BledomError> {
-        self.send_command_bytes(&[0x7e, 0x00, 0x04, 0xf0, 0x00, 0x01, 0xff, 0x00, 0xef])
-            .await
+    // See `PowerOffGuard` for why this is only best-effort.
+    pub fn power_off_on_drop(self) -> PowerOffGuard {
+        PowerOffGuard::new(self)
     }
 
-    pub async fn power_off(&self) -> Result<(), BledomError> {
-        self.send_command_bytes(&[0x7e, 0x00, 0x04, 0x00, 0x00, 0x00, 0xff, 0x00, 0xef])
-            .await
+    // Wraps a peripheral the caller already discovered and connected with btleplug; only
+    // service discovery and the characteristic lookup are run.
+    pub async fn from_peripheral(peripheral: Peripheral) -> Result<BledomDevice, BledomError> {
+        let mut builder = BledomDeviceBuilder::new();
+        builder.peripheral = Some(peripheral);
+        builder.build().await
     }
 
-    pub async fn set_brightness(&self, value: u8) -> Result<(), BledomError> {
-        if value > 0x64 {
-            return Err(BledomError::InvalidParameter(format!(
-                "brightness value {value} out of supported range (0-100)."
-            )));
-        }
-        self.send_command_bytes(&[0x7e, 0x00, 0x01, value, 0x00, 0x00, 0x00, 0x00, 0xef])
-            .await
+    pub fn address(&self) -> BDAddr {
+        self.sink.peripheral.address()
     }
 
-    pub async fn sync_time(&self) -> Result<(), BledomError> {
-        let system_time = chrono::offset::Local::now();
-        let hour = chrono::Timelike::hour(&system_time) as u8;
+    // Advertised name read when the device was built.
+    pub fn name(&self) -> Option<&str> {
+        self.sink.name.as_deref()
+    }
+
+    // Platform description of the adapter `build()` picked (e.g. "hci0 (usb:v1D6Bp0246d0540)"
+    // on BlueZ). Unknown for devices made with `from_peripheral`.
+    pub async fn adapter_info(&self) -> Result<String, BledomError> {
+        let adapter = self.sink.adapter.as_ref().ok_or_else(|| {
+            BledomError::AdapterNotFound("peripheral was supplied by the caller".to_string())
+        })?;
+        Ok(adapter.adapter_info().await?)
+    }
+
+    // Re-runs service discovery and looks the command characteristic up again, e.g. after a
+    // firmware update changed the GATT database, without reconnecting.
+    pub async fn rediscover(&mut self) -> Result<(), BledomError> {
+        let uuid = self.sink.characteristic.uuid;
+        self.sink.characteristic = discover_characteristic(&self.sink.peripheral, uuid).await?;
+        // The keepalive task writes through its own copy of the sink.
+        if let Some(interval) = self.keepalive.as_ref().map(|keepalive| keepalive.interval) {
+            self.start_keepalive(interval)?;
+        }
+        Ok(())
+    }
+
+    pub async fn disconnect(&self) -> Result<(), BledomError> {
+        if let Some(keepalive) = &self.keepalive {
+            keepalive.stop.cancel();
+        }
+        self.sink
+            .peripheral
+            .disconnect()
+            .await
+            .map_err(|e| BledomError::DisconnectFailed(e.to_string()))
+    }
+
+    pub async fn is_connected(&self) -> Result<bool, BledomError> {
+        Ok(self.sink.peripheral.is_connected().await?)
+    }
+
+    pub async fn ensure_connected(&self) -> Result<(), BledomError> {
+        self.sink.ensure_connected().await
+    }
+
+    pub async fn rssi(&self) -> Result<i16, BledomError> {
+        self.sink
+            .peripheral
+            .properties()
+            .await?
+            .ok_or(BledomError::PropertiesUnavailable(self.address()))?
+            .rssi
+            .ok_or(BledomError::Other("RSSI not available".into()))
+    }
+
+    // Only battery-powered variants expose the standard Battery Service; `None` for the rest.
+    pub async fn battery_level(&self) -> Result<Option<u8>, BledomError> {
+        let peripheral = &self.sink.peripheral;
+        let Some(battery) = peripheral
+            .characteristics()
+            .into_iter()
+            .find(|c| c.uuid == BATTERY_LEVEL_CHARACTERISTIC_UUID)
+        else {
+            return Ok(None);
+        };
+        Ok(peripheral.read(&battery).await?.first().copied())
+    }
+
+    // Always `None` for now: the firmware has no known capability query, and the GATT layout
+    // (service 0xFFF0 with 0xFFF3/0xFFF4) is the same on clones that ignore the 0x82/0x83
+    // timer commands and on those that honor them, so there is nothing to detect it from.
+    // Kept so callers can already handle a real answer once one is found.
+    pub async fn supports_scheduling(&self) -> Result<Option<bool>, BledomError> {
+        Ok(None)
+    }
+
+    pub async fn subscribe(&self) -> Result<impl Stream<Item = Vec<u8>>, BledomError> {
+        // Prefer the 0xFFF4 characteristic most firmwares use, but accept any notify one.
+        let peripheral = &self.sink.peripheral;
+        let chars = peripheral.characteristics();
+        let notify_char = chars
+            .iter()
+            .filter(|c| c.properties.contains(CharPropFlags::NOTIFY))
+            .min_by_key(|c| c.uuid != NOTIFY_CHARACTERISTIC_UUID)
+            .ok_or(BledomError::NotifyCharacteristicNotFound)?
+            .to_owned();
+
+        peripheral.subscribe(&notify_char).await?;
+        let notifications = peripheral.notifications().await?;
+        Ok(notifications.filter_map(move |n| {
+            let value = (n.uuid == notify_char.uuid).then_some(n.value);
+            async move { value }
+        }))
+    }
+}
+
+impl<S: CommandSink + Clone + Send + Sync + 'static> BledomDevice<S> {
+    // Starts the task behind `BledomDeviceBuilder::keepalive` on this handle, replacing any
+    // running one, e.g. for a device from `build_with_sink`.
+    pub fn start_keepalive(&mut self, interval: Duration) -> Result<(), BledomError> {
+        if interval.is_zero() {
+            return Err(BledomError::InvalidConfig(
+                "keepalive interval must be non-zero".to_string(),
+            ));
+        }
+        self.keepalive = Some(spawn_keepalive(self, interval));
+        Ok(())
+    }
+}
+
+impl<S: CommandSink> BledomDevice<S> {
+    async fn send_command_bytes(&self, data: &[u8]) -> Result<(), BledomError> {
+        check_framing(data)?;
+        self.write_raw(data).await
+    }
+
+    // Validates `command` and writes its encoding; every typed method goes through here.
+    pub async fn send_command(&self, command: Command) -> Result<(), BledomError> {
+        command.validate()?;
+        let slot = match &self.coalescer {
+            Some(coalescer) => coalescer.slot(&command),
+            None => None,
+        };
+        let Some(slot) = slot else {
+            return self.write_raw(&encoded(&command)).await;
+        };
+        *slot.lock().unwrap() = Some(command);
+        let _queued = self.queue_turn().await;
+        let Some(latest) = slot.lock().unwrap().take() else {
+            debug!(device:% = self.log_id(); "skipping superseded {:?}", command);
+            return Ok(());
+        };
+        self.write_now(&encoded(&latest), None).await.map(|_| ())
+    }
+
+    async fn send_command_with(
+        &self,
+        command: Command,
+        write_type: WriteType,
+    ) -> Result<Delivery, BledomError> {
+        command.validate()?;
+        self.write_raw_with(&encoded(&command), write_type).await
+    }
+
+    // Unlike `generic_command`, no framing is enforced here; the bytes are written as-is.
+    pub async fn write_raw(&self, data: &[u8]) -> Result<(), BledomError> {
+        let _queued = self.queue_turn().await;
+        self.write_now(data, None).await.map(|_| ())
+    }
+
+    // Like `write_raw`, but overrides the default write type for this one write, e.g.
+    // `WithResponse` to make sure a command landed: that returns `Delivery::Acknowledged` once
+    // the strip confirmed it, while a GATT error it reports comes back as `Err`.
+    pub async fn write_raw_with(
+        &self,
+        data: &[u8],
+        write_type: WriteType,
+    ) -> Result<Delivery, BledomError> {
+        let _queued = self.queue_turn().await;
+        self.write_now(data, Some(write_type)).await
+    }
+
+    // Waits for the earlier writes when updates are coalesced; `None` otherwise.
+    async fn queue_turn(&self) -> Option<tokio::sync::MutexGuard<'_, ()>> {
+        match &self.coalescer {
+            Some(coalescer) => Some(coalescer.queue.lock().await),
+            None => None,
+        }
+    }
+
+    // One paced write, for callers that already have their turn in the queue.
+    async fn write_now(
+        &self,
+        data: &[u8],
+        write_type: Option<WriteType>,
+    ) -> Result<Delivery, BledomError> {
+        let _permit = self.rate_limit_permit().await;
+        let delivery = self.write_packet(data, write_type).await?;
+        time::sleep(self.command_delay).await;
+        Ok(delivery)
+    }
+
+    // `None` without a shared limiter (or if it was closed), in which case writes aren't paced
+    // against other devices.
+    async fn rate_limit_permit(&self) -> Option<SemaphorePermit<'_>> {
+        self.rate_limiter.as_ref()?.acquire().await.ok()
+    }
+
+    // A single write, retried as configured, without the trailing command delay.
+    async fn write_packet(
+        &self,
+        data: &[u8],
+        write_type: Option<WriteType>,
+    ) -> Result<Delivery, BledomError> {
+        if let Some(on_command) = &self.on_command {
+            on_command(data);
+        }
+        let mut retry_count = 0;
+        loop {
+            let error = match self.send_once(data, write_type).await {
+                Ok(delivery) => {
+                    trace!(
+                        device:% = self.log_id(), delivery:? = delivery;
+                        "wrote {:02x?}", data
+                    );
+                    self.track(data);
+                    return Ok(delivery);
+                }
+                Err(e) if retry_count >= self.command_retries => return Err(e),
+                Err(e) => e.to_string(),
+            };
+            retry_count += 1;
+            warn!(
+                device:% = self.log_id();
+                "failed to write command, retrying ({retry_count}): {}",
+                error
+            );
+            time::sleep(self.command_delay).await;
+        }
+    }
+
+    async fn send_once(
+        &self,
+        data: &[u8],
+        write_type: Option<WriteType>,
+    ) -> Result<Delivery, BledomError> {
+        match write_type {
+            Some(write_type) => self.sink.send_with(data, write_type).await,
+            None => self.sink.send(data).await.map(|()| Delivery::Sent),
+        }
+    }
+
+    // The `device` key on log records, so output from several strips can be told apart.
+    fn log_id(&self) -> String {
+        self.sink
+            .address()
+            .map_or_else(|| "-".to_string(), |address| address.to_string())
+    }
+
+    fn track(&self, data: &[u8]) {
+        self.tracked.lock().unwrap().record(data);
+    }
+
+    pub fn stop_keepalive(&mut self) {
+        self.keepalive = None;
+    }
+
+    // The last 9-byte packet written successfully through this handle, including raw ones
+    // from `write_raw`; `None` before the first write.
+    pub fn last_command(&self) -> Option<[u8; 9]> {
+        self.tracked.lock().unwrap().last_command
+    }
+
+    // The firmware has no known state query and most clones never push their state over
+    // notify, so this is the last color set through this handle, as passed in (before gamma
+    // correction). `None` until a color has been set; drifts if the remote or another app
+    // changes the light.
+    pub fn current_color(&self) -> Option<(u8, u8, u8)> {
+        let color = self.tracked.lock().unwrap().color;
+        color.map(|color| color.requested)
+    }
+
+    // Same caveats as `current_color`.
+    pub fn current_brightness(&self) -> Option<u8> {
+        self.tracked.lock().unwrap().brightness
+    }
+
+    // Tracked like `current_color`; `None` until a color or effect has been set, and again
+    // after a power-off.
+    pub fn current_mode(&self) -> Option<LightMode> {
+        self.tracked.lock().unwrap().mode
+    }
+
+    pub fn batch(&self) -> CommandBatch<'_, S> {
+        CommandBatch {
+            device: self,
+            commands: Vec::new(),
+        }
+    }
+
+    pub async fn power_on(&self) -> Result<(), BledomError> {
+        self.send_command(Command::PowerOn).await
+    }
+
+    pub async fn power_off(&self) -> Result<(), BledomError> {
+        self.send_command(Command::PowerOff).await
+    }
+
+    pub async fn power_on_with(&self, write_type: WriteType) -> Result<Delivery, BledomError> {
+        self.send_command_with(Command::PowerOn, write_type).await
+    }
+
+    // E.g. `power_off_with(WriteType::WithResponse)` at shutdown, to be sure the strip got it.
+    pub async fn power_off_with(&self, write_type: WriteType) -> Result<Delivery, BledomError> {
+        self.send_command_with(Command::PowerOff, write_type).await
+    }
+
+    // Flips the power state based on the last power command this crate sent, not on what the
+    // strip reports, so it can be out of sync after using the remote. Assumes off initially.
+    pub async fn toggle(&self) -> Result<(), BledomError> {
+        let powered = self.tracked.lock().unwrap().powered;
+        if powered {
+            self.power_off().await
+        } else {
+            self.power_on().await
+        }
+    }
+
+    pub async fn set_brightness(&self, value: u8) -> Result<(), BledomError> {
+        self.send_command(Command::SetBrightness(value)).await
+    }
+
+    pub async fn set_brightness_percent(&self, value: Percent) -> Result<(), BledomError> {
+        self.set_brightness(value.get()).await
+    }
+
+    // For callers on the common 0-255 scale (e.g. Home Assistant); rounds to the nearest
+    // percent, so 0 and 255 map to exactly 0 and 100.
+    pub async fn set_brightness_255(&self, value: u8) -> Result<(), BledomError> {
+        let percent = (value as u16 * 100 + 127) / 255;
+        self.set_brightness(percent as u8).await
+    }
+
+    pub async fn fade_brightness(
+        &self,
+        from: u8,
+        to: u8,
+        duration: Duration,
+    ) -> Result<(), BledomError> {
+        self.fade_brightness_with_cancel(from, to, duration, &CancellationToken::new())
+            .await
+    }
+
+    // Cancellation is checked between steps, so the strip is left at the last full step.
+    pub async fn fade_brightness_with_cancel(
+        &self,
+        from: u8,
+        to: u8,
+        duration: Duration,
+        cancel: &CancellationToken,
+    ) -> Result<(), BledomError> {
+        let from = from.min(0x64);
+        let to = to.min(0x64);
+        if from == to {
+            return self.set_brightness(to).await;
+        }
+        let steps = fade_steps(duration, self.command_delay);
+        for step in 0..=steps {
+            if cancel.is_cancelled() {
+                break;
+            }
+            self.set_brightness(lerp_u8(from, to, step, steps)).await?;
+        }
+        Ok(())
+    }
+
+    // Brightness is lowered while the strip is still off, so powering on doesn't flash the
+    // previous level. Firmware that ignored that write while off gets the fade's first step,
+    // also 0, right after the power-on.
+    pub async fn power_on_fade(&self, to: Percent, duration: Duration) -> Result<(), BledomError> {
+        self.batch().brightness(0).power_on().send().await?;
+        self.fade_brightness(0, to.get(), duration).await
+    }
+
+    #[cfg(feature = "time")]
+    pub async fn sync_time(&self) -> Result<(), BledomError> {
+        let system_time = chrono::offset::Local::now();
+        let hour = chrono::Timelike::hour(&system_time) as u8;
         let minute = chrono::Timelike::minute(&system_time) as u8;
         let second = chrono::Timelike::second(&system_time) as u8;
         let day_of_week = chrono::Datelike::weekday(&system_time).number_from_monday() as u8; // 1 for Monday, 7 for Sunday
-        self.send_command_bytes(&[
-            0x7e,
-            0x00,
-            0x83,
-            hour,
-            minute,
-            second,
-            day_of_week,
-            0x00,
-            0xef,
-        ])
-        .await
+        self.set_custom_time(hour, minute, second, day_of_week)
+            .await
     }
 
     pub async fn set_custom_time(
@@ -299,38 +1593,12 @@ impl BledomDevice {
         second: u8,
         day_of_week: u8, // 1 for Monday, 7 for Sunday
     ) -> Result<(), BledomError> {
-        if hour > 23 {
-            return Err(BledomError::InvalidParameter(format!(
-                "hour value {hour} out of supported range (0-23)."
-            )));
-        }
-        if minute > 59 {
-            return Err(BledomError::InvalidParameter(format!(
-                "minute value {minute} out of supported range (0-59)."
-            )));
-        }
-        if second > 59 {
-            return Err(BledomError::InvalidParameter(format!(
-                "second value {second} out of supported range (0-59)."
-            )));
-        }
-        if !(1..=7).contains(&day_of_week) {
-            return Err(BledomError::InvalidParameter(format!(
-                "day of week value {day_of_week} out of supported range (1-7, 1=Monday)."
-            )));
-        }
-
-        self.send_command_bytes(&[
-            0x7e,
-            0x00,
-            0x83,
+        self.send_command(Command::SetTime {
             hour,
             minute,
             second,
             day_of_week,
-            0x00,
-            0xef,
-        ])
+        })
         .await
     }
 
@@ -340,63 +1608,400 @@ impl BledomDevice {
         green_value: u8,
         blue_value: u8,
     ) -> Result<(), BledomError> {
-        self.send_command_bytes(&[
-            0x7e,
-            0x00,
-            0x05,
-            0x03,
-            red_value,
-            green_value,
-            blue_value,
-            0x00,
-            0xef,
-        ])
-        .await
+        self.set_color_with(red_value, green_value, blue_value, true)
+            .await
     }
 
-    pub async fn set_effect(&self, value: u8) -> Result<(), BledomError> {
-        self.send_command_bytes(&[0x7e, 0x00, 0x03, value, 0x03, 0x00, 0x00, 0x00, 0xef])
-            .await
+    // With `force_static`, a color set while an effect is known to be running is preceded by
+    // the reset from `leave_effect`, since some strips otherwise keep running the effect; pass
+    // `false` to send only the color command.
+    pub async fn set_color_with(
+        &self,
+        red_value: u8,
+        green_value: u8,
+        blue_value: u8,
+        force_static: bool,
+    ) -> Result<(), BledomError> {
+        let command = self.color_command(red_value, green_value, blue_value);
+        command.validate()?;
+        if force_static {
+            self.leave_effect().await?;
+        }
+        self.send_command(command).await
     }
 
-    pub async fn set_effect_speed(&self, value: u8) -> Result<(), BledomError> {
-        if value > 0x64 {
-            return Err(BledomError::InvalidParameter(format!(
-                "effect speed value {value} out of supported range (0-100)."
-            )));
+    // No mode-reset command is known, and some strips ignore the static color command right
+    // after an effect until power is toggled. Re-sending power-on is the part of that toggle
+    // which does the reset without the visible off; it is skipped unless an effect is tracked.
+    async fn leave_effect(&self) -> Result<(), BledomError> {
+        let mode = self.tracked.lock().unwrap().mode;
+        if mode == Some(LightMode::Effect) {
+            debug!(device:% = self.log_id(); "leaving effect mode before setting a static color");
+            self.send_command(Command::PowerOn).await?;
         }
-        self.send_command_bytes(&[0x7e, 0x00, 0x02, value, 0x00, 0x00, 0x00, 0x00, 0xef])
-            .await
+        Ok(())
     }
 
-    pub async fn set_schedule_on(
+    // Whichever write lowers the light output goes first: when dimming (or if the current
+    // brightness is unknown) brightness is set before the color, otherwise after it. That keeps
+    // the 100ms gap from showing the new color at the old, brighter level.
+    pub async fn set_color_brightness(
         &self,
-        days: u8,
-        hours: u8,
-        minutes: u8,
-        enabled: bool,
+        rgb: (u8, u8, u8),
+        brightness: Percent,
+    ) -> Result<(), BledomError> {
+        let (r, g, b) = rgb;
+        let current = self.tracked.lock().unwrap().brightness;
+        let batch = self.batch();
+        let batch = match current {
+            Some(current) if current < brightness.get() => {
+                batch.color(r, g, b).brightness(brightness.get())
+            }
+            _ => batch.brightness(brightness.get()).color(r, g, b),
+        };
+        batch.send().await
+    }
+
+    fn color_command(&self, red_value: u8, green_value: u8, blue_value: u8) -> Command {
+        let wire = (
+            gamma_correct(red_value, self.gamma),
+            gamma_correct(green_value, self.gamma),
+            gamma_correct(blue_value, self.gamma),
+        );
+        self.tracked.lock().unwrap().pending_color = Some(TrackedColor {
+            wire,
+            requested: (red_value, green_value, blue_value),
+        });
+        self.raw_color_command(wire.0, wire.1, wire.2)
+    }
+
+    // Reapplies a tracked color; its wire value already went through gamma correction, so it
+    // is written as-is.
+    fn restore_color_command(&self, color: TrackedColor) -> Command {
+        self.tracked.lock().unwrap().pending_color = Some(color);
+        let (red, green, blue) = color.wire;
+        self.raw_color_command(red, green, blue)
+    }
+
+    // Without gamma correction, for colors that already went through it.
+    fn raw_color_command(&self, red: u8, green: u8, blue: u8) -> Command {
+        if self.color_mode == COLOR_MODE_RGB {
+            return Command::SetColor(red, green, blue);
+        }
+        Command::SetColorWithMode {
+            mode: self.color_mode,
+            red,
+            green,
+            blue,
+        }
+    }
+
+    // The 0x05 color command is `7e 00 05 <mode> <a> <b> <c> 00 ef`, where mode 0x03 takes
+    // red/green/blue and mode 0x01 drives the dedicated white channel of RGBW strips with the
+    // level in byte 4. Plain RGB strips ignore mode 0x01.
+    pub async fn set_white(&self, value: u8) -> Result<(), BledomError> {
+        self.send_command(Command::SetWhite(value)).await
+    }
+
+    pub async fn set_color_rgbw(
+        &self,
+        red_value: u8,
+        green_value: u8,
+        blue_value: u8,
+        white_value: u8,
     ) -> Result<(), BledomError> {
-        if days > WEEK_DAYS.all {
+        self.set_color(red_value, green_value, blue_value).await?;
+        self.set_white(white_value).await
+    }
+
+    pub async fn set_named_color(&self, name: &str) -> Result<(), BledomError> {
+        let (red_value, green_value, blue_value) = named_color(name).ok_or_else(|| {
+            BledomError::InvalidParameter(format!("unknown color name {name:?}."))
+        })?;
+        self.set_color(red_value, green_value, blue_value).await
+    }
+
+    // Kelvin values outside 1000-12000 are clamped into that range rather than rejected.
+    pub async fn set_color_temperature(&self, kelvin: u16) -> Result<(), BledomError> {
+        let (red_value, green_value, blue_value) = kelvin_to_rgb(kelvin.clamp(1000, 12000));
+        self.set_color(red_value, green_value, blue_value).await
+    }
+
+    pub async fn warm_white(&self) -> Result<(), BledomError> {
+        let (red_value, green_value, blue_value) = WARM_WHITE;
+        self.set_color(red_value, green_value, blue_value).await
+    }
+
+    pub async fn daylight_white(&self) -> Result<(), BledomError> {
+        let (red_value, green_value, blue_value) = DAYLIGHT_WHITE;
+        self.set_color(red_value, green_value, blue_value).await
+    }
+
+    pub async fn cool_white(&self) -> Result<(), BledomError> {
+        let (red_value, green_value, blue_value) = COOL_WHITE;
+        self.set_color(red_value, green_value, blue_value).await
+    }
+
+    pub async fn set_color_hsv(
+        &self,
+        hue: f32,
+        saturation: f32,
+        value: f32,
+    ) -> Result<(), BledomError> {
+        if !(0.0..360.0).contains(&hue) {
             return Err(BledomError::InvalidParameter(format!(
-                "days bitmask {days:#02x} is invalid (max 0x7F)."
+                "hue value {hue} out of supported range (0-360)."
             )));
         }
-        if hours > 23 {
+        if !(0.0..=1.0).contains(&saturation) {
             return Err(BledomError::InvalidParameter(format!(
-                "hour value {hours} out of supported range (0-23)."
+                "saturation value {saturation} out of supported range (0.0-1.0)."
             )));
         }
-        if minutes > 59 {
+        if !(0.0..=1.0).contains(&value) {
             return Err(BledomError::InvalidParameter(format!(
-                "minute value {minutes} out of supported range (0-59)."
+                "value {value} out of supported range (0.0-1.0)."
             )));
         }
+        let (red_value, green_value, blue_value) = hsv_to_rgb(hue, saturation, value);
+        self.set_color(red_value, green_value, blue_value).await
+    }
+
+    pub async fn set_color_hex(&self, hex: &str) -> Result<(), BledomError> {
+        let (red_value, green_value, blue_value) = parse_hex_color(hex)?;
+        self.set_color(red_value, green_value, blue_value).await
+    }
+
+    pub async fn fade_color(
+        &self,
+        from: (u8, u8, u8),
+        to: (u8, u8, u8),
+        duration: Duration,
+    ) -> Result<(), BledomError> {
+        self.fade_color_with_cancel(from, to, duration, &CancellationToken::new())
+            .await
+    }
+
+    // Cancellation is checked between steps, so the strip is left at the last full step.
+    pub async fn fade_color_with_cancel(
+        &self,
+        from: (u8, u8, u8),
+        to: (u8, u8, u8),
+        duration: Duration,
+        cancel: &CancellationToken,
+    ) -> Result<(), BledomError> {
+        if from == to {
+            return self.set_color(to.0, to.1, to.2).await;
+        }
+        let steps = fade_steps(duration, self.command_delay);
+        for step in 0..=steps {
+            if cancel.is_cancelled() {
+                break;
+            }
+            self.set_color(
+                lerp_u8(from.0, to.0, step, steps),
+                lerp_u8(from.1, to.1, step, steps),
+                lerp_u8(from.2, to.2, step, steps),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    // Alternates between `color` and black `times` times. Afterwards the color last written
+    // through this handle (see `current_color`) is restored; if none was, the strip stays black.
+    pub async fn flash(
+        &self,
+        color: (u8, u8, u8),
+        times: u32,
+        on: Duration,
+        off: Duration,
+    ) -> Result<(), BledomError> {
+        let previous = self.tracked.lock().unwrap().color;
+        for _ in 0..times {
+            self.set_color(color.0, color.1, color.2).await?;
+            time::sleep(on).await;
+            self.set_color(0, 0, 0).await?;
+            time::sleep(off).await;
+        }
+        if let Some(previous) = previous {
+            self.send_command(self.restore_color_command(previous))
+                .await?;
+        }
+        Ok(())
+    }
+
+    // Each cycle sweeps the hue in 6 degree steps. Dropping the future between steps simply
+    // leaves the strip at the last color written.
+    pub async fn rainbow(&self, cycles: u32, step_delay: Duration) -> Result<(), BledomError> {
+        self.rainbow_with_cancel(cycles, step_delay, &CancellationToken::new())
+            .await
+    }
+
+    // Stops at the current hue once `cancel` fires; a write already in flight is completed.
+    pub async fn rainbow_with_cancel(
+        &self,
+        cycles: u32,
+        step_delay: Duration,
+        cancel: &CancellationToken,
+    ) -> Result<(), BledomError> {
+        for _ in 0..cycles {
+            for hue in (0..360).step_by(6) {
+                let (red_value, green_value, blue_value) = hsv_to_rgb(hue as f32, 1.0, 1.0);
+                self.set_color(red_value, green_value, blue_value).await?;
+                tokio::select! {
+                    _ = cancel.cancelled() => return Ok(()),
+                    _ = time::sleep(step_delay) => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn set_effect(&self, value: u8) -> Result<(), BledomError> {
+        self.send_command(Command::SetEffect(value)).await
+    }
+
+    pub async fn set_effect_typed(&self, effect: Effect) -> Result<(), BledomError> {
+        self.set_effect(effect.code()).await
+    }
+
+    // There is no dedicated "effect off" command, so this sets a static color the way
+    // `set_color` does, including the reset from `leave_effect`. The color last written
+    // through this handle is reapplied, or white if there is none.
+    pub async fn stop_effect(&self) -> Result<(), BledomError> {
+        let color = self.tracked.lock().unwrap().color;
+        let color = color.unwrap_or(TrackedColor {
+            wire: (0xff, 0xff, 0xff),
+            requested: (0xff, 0xff, 0xff),
+        });
+        self.leave_effect().await?;
+        self.send_command(self.restore_color_command(color)).await
+    }
+
+    pub async fn set_effect_speed(&self, value: u8) -> Result<(), BledomError> {
+        self.send_command(Command::SetEffectSpeed(value)).await
+    }
+
+    pub async fn set_effect_speed_percent(&self, value: Percent) -> Result<(), BledomError> {
+        self.set_effect_speed(value.get()).await
+    }
+
+    // Steps the speed like `fade_brightness` steps brightness, ending exactly on `to`.
+    pub async fn ramp_effect_speed(
+        &self,
+        from: Percent,
+        to: Percent,
+        duration: Duration,
+    ) -> Result<(), BledomError> {
+        let (from, to) = (from.get(), to.get());
+        if from == to {
+            return self.set_effect_speed(to).await;
+        }
+        let steps = fade_steps(duration, self.command_delay);
+        for step in 0..=steps {
+            self.set_effect_speed(lerp_u8(from, to, step, steps))
+                .await?;
+        }
+        Ok(())
+    }
 
-        let value = if enabled { days + 0x80 } else { days };
-        self.send_command_bytes(&[0x7e, 0x00, 0x82, hours, minutes, 0x00, 0x00, value, 0xef])
+    pub async fn set_effect_with_speed(
+        &self,
+        effect: Effect,
+        speed: Percent,
+    ) -> Result<(), BledomError> {
+        self.batch()
+            .effect_typed(effect)
+            .effect_speed(speed.get())
+            .send()
             .await
     }
 
+    // Enables the microphone, then selects the mode and sensitivity (0-100). Strips without
+    // a microphone ignore these.
+    pub async fn set_music_mode(
+        &self,
+        mode: MusicMode,
+        sensitivity: u8,
+    ) -> Result<(), BledomError> {
+        let commands = [
+            Command::EnableMic(true),
+            Command::SetMusicMode(mode.code()),
+            Command::SetMusicSensitivity(sensitivity),
+        ];
+        commands.iter().try_for_each(Command::validate)?;
+        for command in commands {
+            self.send_command(command).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn disable_music_mode(&self) -> Result<(), BledomError> {
+        self.send_command(Command::EnableMic(false)).await
+    }
+
+    // Applies power first, then color, brightness and effect. Powering off skips the rest,
+    // since the strip ignores them while off.
+    pub async fn apply_state(&self, state: &LightState) -> Result<(), BledomError> {
+        match state.power {
+            Some(true) => self.power_on().await?,
+            Some(false) => return self.power_off().await,
+            None => {}
+        }
+        if let Some((red_value, green_value, blue_value)) = state.rgb {
+            self.set_color(red_value, green_value, blue_value).await?;
+        }
+        if let Some(brightness) = state.brightness {
+            self.set_brightness(brightness).await?;
+        }
+        if let Some(effect) = state.effect {
+            self.set_effect_typed(effect).await?;
+        }
+        if let Some(effect_speed) = state.effect_speed {
+            self.set_effect_speed(effect_speed).await?;
+        }
+        Ok(())
+    }
+
+    // Sent as one batch: the effect, then its speed, then brightness.
+    pub async fn apply_effect_scene(&self, scene: &EffectScene) -> Result<(), BledomError> {
+        self.batch()
+            .effect_typed(scene.effect)
+            .effect_speed(scene.speed.get())
+            .brightness(scene.brightness.get())
+            .send()
+            .await
+    }
+
+    pub async fn set_schedule(
+        &self,
+        kind: ScheduleKind,
+        schedule: &Schedule,
+    ) -> Result<(), BledomError> {
+        self.send_command(Command::SetSchedule {
+            kind,
+            schedule: *schedule,
+        })
+        .await
+    }
+
+    pub async fn set_schedule_on(
+        &self,
+        days: u8,
+        hours: u8,
+        minutes: u8,
+        enabled: bool,
+    ) -> Result<(), BledomError> {
+        let schedule = Schedule {
+            days,
+            hour: hours,
+            minute: minutes,
+            enabled,
+        };
+        self.set_schedule(ScheduleKind::On, &schedule).await
+    }
+
     pub async fn set_schedule_off(
         &self,
         days: u8,
@@ -404,26 +2009,26 @@ impl BledomDevice {
         minutes: u8,
         enabled: bool,
     ) -> Result<(), BledomError> {
-        // Days are bit flags, valid range 0x00-0x7F (all bits 0-6 set for Monday-Sunday)
-        if days > WEEK_DAYS.all {
-            return Err(BledomError::InvalidParameter(format!(
-                "days bitmask {days:#02x} is invalid (max 0x7F)."
-            )));
-        }
-        if hours > 23 {
-            return Err(BledomError::InvalidParameter(format!(
-                "hour value {hours} out of supported range (0-23)."
-            )));
-        }
-        if minutes > 59 {
-            return Err(BledomError::InvalidParameter(format!(
-                "minute value {minutes} out of supported range (0-59)."
-            )));
-        }
+        let schedule = Schedule {
+            days,
+            hour: hours,
+            minute: minutes,
+            enabled,
+        };
+        self.set_schedule(ScheduleKind::Off, &schedule).await
+    }
 
-        let value = if enabled { days + 0x80 } else { days };
-        self.send_command_bytes(&[0x7e, 0x00, 0x82, hours, minutes, 0x00, 0x01, value, 0xef])
-            .await
+    // Disables both timers by sending them with no days and the enable bit cleared:
+    // `7e 00 82 00 00 00 00 00 ef` (on) then `7e 00 82 00 00 00 01 00 ef` (off).
+    pub async fn clear_schedules(&self) -> Result<(), BledomError> {
+        let disabled = Schedule {
+            days: 0,
+            hour: 0,
+            minute: 0,
+            enabled: false,
+        };
+        self.set_schedule(ScheduleKind::On, &disabled).await?;
+        self.set_schedule(ScheduleKind::Off, &disabled).await
     }
 
     pub async fn generic_command(
@@ -437,34 +2042,728 @@ impl BledomDevice {
         self.send_command_bytes(&[0x7e, 0x00, id, sub_id, arg1, arg2, arg3, 0x00, 0xef])
             .await
     }
+
+    // Full control over every byte between the 0x7e prefix and the 0xef suffix, for
+    // experimenting with undocumented commands.
+    pub async fn generic_command9(&self, payload: [u8; 7]) -> Result<(), BledomError> {
+        let mut data = [PACKET_START, 0, 0, 0, 0, 0, 0, 0, PACKET_END];
+        data[1..8].copy_from_slice(&payload);
+        self.send_command_bytes(&data).await
+    }
+}
+
+// Number of intervals a fade over `duration` is split into; every interval costs one write.
+fn fade_steps(duration: Duration, delay: Duration) -> u32 {
+    let steps = duration.as_millis() / delay.as_millis().max(1);
+    steps.clamp(1, u32::MAX as u128) as u32
+}
+
+// Linear interpolation that lands exactly on `to` when `step == steps`.
+fn lerp_u8(from: u8, to: u8, step: u32, steps: u32) -> u8 {
+    let delta = (to as i64 - from as i64) * step as i64;
+    let offset = (delta as f64 / steps as f64).round() as i64;
+    (from as i64 + offset) as u8
+}
+
+// Accumulates commands and writes them back to back, sleeping only between writes. All
+// parameters are validated by `send` before anything is written.
+pub struct CommandBatch<'a, S = BleSink> {
+    device: &'a BledomDevice<S>,
+    commands: Vec<Command>,
+}
+
+impl<S: CommandSink> CommandBatch<'_, S> {
+    pub fn command(mut self, command: Command) -> Self {
+        self.commands.push(command);
+        self
+    }
+
+    pub fn power_on(self) -> Self {
+        self.command(Command::PowerOn)
+    }
+
+    pub fn power_off(self) -> Self {
+        self.command(Command::PowerOff)
+    }
+
+    pub fn color(self, red_value: u8, green_value: u8, blue_value: u8) -> Self {
+        let command = self
+            .device
+            .color_command(red_value, green_value, blue_value);
+        self.command(command)
+    }
+
+    pub fn brightness(self, value: u8) -> Self {
+        self.command(Command::SetBrightness(value))
+    }
+
+    pub fn effect(self, value: u8) -> Self {
+        self.command(Command::SetEffect(value))
+    }
+
+    pub fn effect_typed(self, effect: Effect) -> Self {
+        self.effect(effect.code())
+    }
+
+    pub fn effect_speed(self, value: u8) -> Self {
+        self.command(Command::SetEffectSpeed(value))
+    }
+
+    // Colors go through the same reset as `set_color`: a power-on goes before any color that
+    // would land while an effect is running, counting effects earlier in the batch.
+    fn with_effect_resets(&self) -> Vec<Command> {
+        let mut state = *self.device.tracked.lock().unwrap();
+        let mut commands = Vec::with_capacity(self.commands.len());
+        for &command in &self.commands {
+            let color = matches!(
+                command,
+                Command::SetColor(..) | Command::SetColorWithMode { .. }
+            );
+            if color && state.mode == Some(LightMode::Effect) {
+                commands.push(Command::PowerOn);
+            }
+            state.record(&command.encode());
+            commands.push(command);
+        }
+        commands
+    }
+
+    pub async fn send(self) -> Result<(), BledomError> {
+        self.commands.iter().try_for_each(Command::validate)?;
+        // The whole batch takes one turn, so queued writes can't land in between.
+        let _queued = self.device.queue_turn().await;
+        let commands = self.with_effect_resets();
+        for (i, command) in commands.iter().enumerate() {
+            let permit = self.device.rate_limit_permit().await;
+            self.device.write_packet(&encoded(command), None).await?;
+            // The last delay is only needed to pace other devices sharing the limiter.
+            if i + 1 < commands.len() || permit.is_some() {
+                time::sleep(self.device.command_delay).await;
+            }
+        }
+        Ok(())
+    }
 }
 
-async fn get_central(manager: &Manager) -> Result<Adapter, BledomError> {
+// `Command::encode` always frames its packets, so the check is only asserted in debug builds.
+// It costs about 1ns per write in release builds anyway, next to the 100ms command delay; the
+// public byte-level paths (`generic_command`) keep checking unconditionally.
+fn encoded(command: &Command) -> [u8; 9] {
+    let packet = command.encode();
+    debug_assert!(check_framing(&packet).is_ok(), "{command:?} is misframed");
+    packet
+}
+
+fn check_framing(data: &[u8]) -> Result<(), BledomError> {
+    if data.len() != 9 || data[0] != PACKET_START || data[8] != PACKET_END {
+        return Err(BledomError::InvalidParameter("malformed command byte array (expected 9 bytes, starting with 0x7e and ending with 0xef)".to_string()));
+    }
+    Ok(())
+}
+
+// Bounds a retry loop by an attempt count, a timeout, or both, whichever is hit first.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RetryPolicy {
+    retries: Option<u8>,
+    timeout: Option<Duration>,
+    interval: Duration,
+    backoff: bool,
+}
+
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+impl RetryPolicy {
+    fn new(retries: Option<u8>, timeout: Option<Duration>, interval: Duration) -> Self {
+        // The default of 10 retries only applies when no timeout was given either.
+        let retries = match (retries, timeout) {
+            (None, None) => Some(10),
+            (retries, _) => retries,
+        };
+        Self {
+            retries,
+            timeout,
+            interval,
+            backoff: false,
+        }
+    }
+
+    // Delay before the next attempt after `failures` failed ones. With backoff the interval
+    // doubles per failure up to `MAX_BACKOFF`, plus up to 25% jitter so several clients don't
+    // keep colliding at the same cadence.
+    fn delay(&self, failures: u32) -> Duration {
+        if !self.backoff {
+            return self.interval;
+        }
+        let doubled = self
+            .interval
+            .saturating_mul(1 << failures.saturating_sub(1).min(16));
+        let delay = doubled.min(MAX_BACKOFF.max(self.interval));
+        // Cheap jitter source; this only needs to differ between clients, not be random.
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.subsec_nanos());
+        delay + delay.mul_f64(f64::from(nanos % 1000) / 4000.0)
+    }
+
+    fn exhausted(&self, attempts: u32, started: Instant) -> bool {
+        self.retries.is_some_and(|r| attempts >= r as u32)
+            || self.timeout.is_some_and(|t| started.elapsed() >= t)
+    }
+}
+
+// Pings go through `on_command` and the same sink, rate limiter and coalescing queue as regular
+// commands, but aren't retried; a failed ping is simply tried again next interval. Real commands
+// push the next ping back by updating `last_write`.
+fn spawn_keepalive<S>(device: &BledomDevice<S>, interval: Duration) -> Keepalive
+where
+    S: CommandSink + Clone + Send + Sync + 'static,
+{
+    let stop = CancellationToken::new();
+    let cancelled = stop.clone();
+    let sink = device.sink.clone();
+    let tracked = Arc::clone(&device.tracked);
+    let rate_limiter = device.rate_limiter.clone();
+    let coalescer = device.coalescer.clone();
+    let on_command = device.on_command.clone();
+    let command_delay = device.command_delay;
+    let log_id = device.log_id();
+    tokio::spawn(async move {
+        // The command to re-send if nothing was written for `interval`; otherwise `None`, with
+        // `wait` shortened to when that will be the case.
+        let due = |wait: &mut Duration| {
+            let tracked = tracked.lock().unwrap();
+            let idle = tracked.last_write.map_or(interval, |t| t.elapsed());
+            if idle < interval {
+                *wait = interval - idle;
+                return None;
+            }
+            match tracked.brightness {
+                Some(brightness) => Some(Command::SetBrightness(brightness)),
+                None if tracked.powered && tracked.mode != Some(LightMode::Effect) => {
+                    Some(Command::PowerOn)
+                }
+                None => None,
+            }
+        };
+        let mut wait = interval;
+        loop {
+            tokio::select! {
+                _ = cancelled.cancelled() => return,
+                _ = time::sleep(wait) => {}
+            }
+            wait = interval;
+            let Some(ping) = due(&mut wait) else {
+                continue;
+            };
+            let _queued = match &coalescer {
+                Some(coalescer) => Some(coalescer.queue.lock().await),
+                None => None,
+            };
+            let _permit = match &rate_limiter {
+                Some(limiter) => limiter.acquire().await.ok(),
+                None => None,
+            };
+            // A command may have gone out while this waited for its turn.
+            if due(&mut wait).is_none() {
+                continue;
+            }
+            let packet = ping.encode();
+            if let Some(on_command) = &on_command {
+                on_command(&packet);
+            }
+            match sink.send(&packet).await {
+                Ok(()) => {
+                    tracked.lock().unwrap().record(&packet);
+                }
+                Err(e) => debug!(device:% = log_id; "keepalive failed: {}", e),
+            }
+            time::sleep(command_delay).await;
+        }
+    });
+    Keepalive { stop, interval }
+}
+
+pub(crate) async fn connect_light(
+    peripheral: &Peripheral,
+    policy: RetryPolicy,
+    on_progress: Option<&(dyn Fn(Progress) + Send + Sync)>,
+) -> Result<(), BledomError> {
+    let started = Instant::now();
+    let mut connect_count = 0;
+    loop {
+        info!(device:% = peripheral.address(); "trying to connect to light");
+        if let Some(on_progress) = on_progress {
+            on_progress(Progress::Connecting {
+                attempt: connect_count + 1,
+            });
+        }
+        let attempt = match policy.timeout {
+            Some(t) => time::timeout(t.saturating_sub(started.elapsed()), peripheral.connect())
+                .await
+                .unwrap_or_else(|_| Err(btleplug::Error::TimedOut(t))),
+            None => peripheral.connect().await,
+        };
+        // Flaky stacks can report a successful connect for a link that is already gone, so
+        // only trust it once the peripheral agrees. The reverse happens too: macOS fails the
+        // connect when the peripheral is already connected (by this or another process), but
+        // the link is usable.
+        let (message, source) = match attempt {
+            Ok(_) => match peripheral.is_connected().await {
+                Ok(true) => return Ok(()),
+                Ok(false) => (
+                    "peripheral disconnected right after connecting".to_string(),
+                    None,
+                ),
+                Err(e) => (e.to_string(), Some(e)),
+            },
+            Err(e) => {
+                if let Ok(true) = peripheral.is_connected().await {
+                    debug!(device:% = peripheral.address(); "connect failed ({}), but already connected", e);
+                    return Ok(());
+                }
+                (e.to_string(), Some(e))
+            }
+        };
+        warn!(device:% = peripheral.address(); "failed to connect light: {}", message);
+        connect_count += 1;
+        if policy.exhausted(connect_count, started) {
+            return Err(BledomError::ConnectionFailed { message, source });
+        }
+        time::sleep(policy.delay(connect_count)).await;
+    }
+}
+
+pub(crate) async fn discover_characteristic(
+    peripheral: &Peripheral,
+    uuid: Uuid,
+) -> Result<Characteristic, BledomError> {
+    peripheral
+        .discover_services()
+        .await
+        .map_err(|e| BledomError::ServiceDiscoveryError(e.to_string()))?;
+
+    peripheral
+        .characteristics()
+        .into_iter()
+        .find(|c| c.uuid == uuid)
+        .ok_or(BledomError::CharacteristicNotFound)
+}
+
+// Most strips take write-without-response, but some clones only accept acknowledged writes.
+fn detect_write_type(
+    characteristic: &Characteristic,
+    address: BDAddr,
+) -> Result<WriteType, BledomError> {
+    let properties = characteristic.properties;
+    if properties.contains(CharPropFlags::WRITE_WITHOUT_RESPONSE) {
+        Ok(WriteType::WithoutResponse)
+    } else if properties.contains(CharPropFlags::WRITE) {
+        warn!(
+            device:% = address;
+            "light characteristic lacks write-without-response, falling back to write"
+        );
+        Ok(WriteType::WithResponse)
+    } else {
+        Err(BledomError::CharacteristicNotWritable)
+    }
+}
+
+fn parse_hex_color(hex: &str) -> Result<(u8, u8, u8), BledomError> {
+    let digits = hex.strip_prefix('#').unwrap_or(hex);
+    let invalid = || {
+        BledomError::InvalidParameter(format!(
+            "hex color {hex:?} is invalid (expected #RRGGBB, RRGGBB or #RGB)."
+        ))
+    };
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let channel = |s: &str| u8::from_str_radix(s, 16).map_err(|_| invalid());
+    match digits.len() {
+        6 => Ok((
+            channel(&digits[0..2])?,
+            channel(&digits[2..4])?,
+            channel(&digits[4..6])?,
+        )),
+        // #RGB shorthand: each digit is doubled, so f expands to ff.
+        3 => Ok((
+            channel(&digits[0..1])? * 0x11,
+            channel(&digits[1..2])? * 0x11,
+            channel(&digits[2..3])? * 0x11,
+        )),
+        _ => Err(invalid()),
+    }
+}
+
+fn gamma_correct(value: u8, gamma: f32) -> u8 {
+    if gamma == 1.0 {
+        return value;
+    }
+    ((value as f32 / 255.0).powf(gamma) * 255.0).round() as u8
+}
+
+fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> (u8, u8, u8) {
+    let chroma = value * saturation;
+    let sector = hue / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u8 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    let to_byte = |c: f32| ((c + m) * 255.0).round() as u8;
+    (to_byte(r), to_byte(g), to_byte(b))
+}
+
+// Returns the adapter's description if it reports being powered off; platforms that can't
+// tell are treated as available.
+async fn powered_off_adapter(central: &Adapter) -> Option<String> {
+    match central.adapter_state().await {
+        Ok(CentralState::PoweredOff) => Some(
+            central
+                .adapter_info()
+                .await
+                .unwrap_or_else(|_| "unknown adapter".to_string()),
+        ),
+        _ => None,
+    }
+}
+
+async fn get_central(
+    manager: &Manager,
+    index: Option<usize>,
+    name: Option<&str>,
+) -> Result<Adapter, BledomError> {
     debug!("getting adapters...");
-    let adapters = manager.adapters().await?;
+    let mut adapters = manager.adapters().await?;
 
     debug!("adapters:\n{:#?}", adapters);
     if adapters.is_empty() {
         error!("no adapters found");
-        Err(BledomError::NoAdaptersFound)
-    } else {
-        Ok(adapters.into_iter().next().unwrap())
+        return Err(BledomError::NoAdaptersFound);
     }
+
+    if let Some(name) = name {
+        let mut matching = Vec::new();
+        for adapter in adapters {
+            if adapter.adapter_info().await?.contains(name) {
+                matching.push(adapter);
+            }
+        }
+        if matching.is_empty() {
+            error!("no adapter matching {name:?} found");
+            return Err(BledomError::AdapterNotFound(format!(
+                "no adapter named {name:?}"
+            )));
+        }
+        adapters = matching;
+    }
+
+    let index = index.unwrap_or(0);
+    if index >= adapters.len() {
+        error!("adapter index {index} out of range");
+        return Err(BledomError::AdapterNotFound(format!(
+            "no adapter at index {index} ({} available)",
+            adapters.len()
+        )));
+    }
+    Ok(adapters.swap_remove(index))
+}
+
+async fn find_known_light(central: &Adapter, address: BDAddr) -> Result<Peripheral, BledomError> {
+    central
+        .peripherals()
+        .await?
+        .into_iter()
+        .find(|p| p.address() == address)
+        .ok_or(BledomError::DeviceNotFound)
+}
+
+pub async fn find_light(
+    central: &Adapter,
+    address: Option<BDAddr>,
+    name_filter: Option<&(dyn Fn(&str) -> bool + Send + Sync)>,
+) -> Result<Peripheral, BledomError> {
+    let (lights, unresolved) = match_lights(central, address, name_filter).await?;
+    lights
+        .into_iter()
+        .next()
+        .ok_or_else(|| not_found(unresolved))
+}
+
+// `PropertiesUnavailable` when nothing matched but some peripheral reported no properties at
+// all, which usually points at the adapter or missing permissions rather than a strip that is
+// off or out of range.
+fn not_found(unresolved: Option<BDAddr>) -> BledomError {
+    unresolved.map_or(
+        BledomError::DeviceNotFound,
+        BledomError::PropertiesUnavailable,
+    )
+}
+
+// The second half of `build()`, for when scanning is driven separately: connects to
+// `peripheral` (e.g. one picked from `find_lights` results) with the connection settings of
+// `opts`, then discovers the characteristic. Scan and adapter selection options are ignored;
+// pass the adapter with `with_adapter` to keep `adapter_info` working.
+pub async fn connect(
+    peripheral: Peripheral,
+    opts: BledomDeviceBuilder,
+) -> Result<BledomDevice, BledomError> {
+    opts.connect_peripheral(peripheral).await
+}
+
+// Every peripheral the adapter currently knows that matches `address`/`name_filter`, in the
+// adapter's order.
+pub async fn find_lights(
+    central: &Adapter,
+    address: Option<BDAddr>,
+    name_filter: Option<&(dyn Fn(&str) -> bool + Send + Sync)>,
+) -> Result<Vec<Peripheral>, BledomError> {
+    Ok(match_lights(central, address, name_filter).await?.0)
 }
 
-pub async fn find_light(central: &Adapter) -> Result<Peripheral, BledomError> {
+// Like `find_lights`, plus the address of a peripheral that reported no properties, if any.
+async fn match_lights(
+    central: &Adapter,
+    address: Option<BDAddr>,
+    name_filter: Option<&(dyn Fn(&str) -> bool + Send + Sync)>,
+) -> Result<(Vec<Peripheral>, Option<BDAddr>), BledomError> {
+    let mut lights = Vec::new();
+    let mut unresolved = None;
     for p in central.peripherals().await? {
-        if p.properties()
-            .await?
-            .ok_or(BledomError::Other(
-                "Peripheral properties not available".into(),
-            ))?
-            .local_name
-            .iter()
-            .any(|name| name.contains("ELK-BLEDOM"))
-        {
-            return Ok(p);
+        match is_light(&p, address, name_filter).await {
+            Match::Light => lights.push(p),
+            Match::NoProperties => unresolved = unresolved.or(Some(p.address())),
+            Match::Other => {}
+        }
+    }
+    Ok((lights, unresolved))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Match {
+    Light,
+    Other,
+    NoProperties,
+}
+
+async fn log_new_peripherals(central: &Adapter, logged: &mut HashSet<BDAddr>) {
+    let Ok(peripherals) = central.peripherals().await else {
+        return;
+    };
+    for p in peripherals {
+        if !logged.insert(p.address()) {
+            continue;
+        }
+        let properties = p.properties().await.ok().flatten();
+        info!(
+            device:% = p.address();
+            "discovered {} name={:?} rssi={:?}",
+            p.address(),
+            properties.as_ref().and_then(|p| p.local_name.as_deref()),
+            properties.as_ref().and_then(|p| p.rssi)
+        );
+    }
+}
+
+async fn is_light(
+    p: &Peripheral,
+    address: Option<BDAddr>,
+    name_filter: Option<&(dyn Fn(&str) -> bool + Send + Sync)>,
+) -> Match {
+    // An explicit address always wins over the name match, since several strips can
+    // advertise the exact same name.
+    if let Some(address) = address {
+        return if p.address() == address {
+            Match::Light
+        } else {
+            Match::Other
+        };
+    }
+    // Peripherals that are still resolving may fail to report properties; skip them rather
+    // than abort the whole scan.
+    let properties = match p.properties().await {
+        Ok(Some(properties)) => properties,
+        Ok(None) => {
+            debug!(device:% = p.address(); "no properties for {}", p.address());
+            return Match::NoProperties;
+        }
+        Err(e) => {
+            debug!(
+                device:% = p.address();
+                "failed to read properties of {}, skipping: {}",
+                p.address(),
+                e
+            );
+            return Match::Other;
+        }
+    };
+    let matched = properties.local_name.iter().any(|name| match name_filter {
+        Some(filter) => filter(name),
+        None => name.contains(DEVICE_NAME),
+    });
+    if matched {
+        Match::Light
+    } else {
+        Match::Other
+    }
+}
+
+pub async fn discover_all(scan_duration: Duration) -> Result<Vec<DiscoveredDevice>, BledomError> {
+    let manager = Manager::new().await?;
+    let central = get_central(&manager, None, None).await?;
+
+    central
+        .start_scan(ScanFilter::default())
+        .await
+        .map_err(|e| BledomError::scan(None, e))?;
+    time::sleep(scan_duration).await;
+
+    let found = collect_discovered(&central).await;
+    central
+        .stop_scan()
+        .await
+        .map_err(|e| BledomError::scan(Some("failed to stop scan"), e))?;
+    let mut found = found?;
+
+    // Devices without a reported RSSI sort last.
+    found.sort_by_key(|d| std::cmp::Reverse(d.rssi));
+    debug!("discovered devices:\n{:#?}", found);
+    Ok(found)
+}
+
+// Yields each strip once, as the adapter first reports it, until `duration` has elapsed; the
+// scan is stopped when the stream ends or is dropped early (like `PowerOffGuard`, the latter
+// only spawns the stop on the current runtime). Without adapter events the known peripherals are
+// polled instead, so strips then show up with up to half a second of delay.
+pub async fn scan_stream(
+    duration: Duration,
+) -> Result<impl Stream<Item = DiscoveredDevice>, BledomError> {
+    let manager = Manager::new().await?;
+    let central = get_central(&manager, None, None).await?;
+    let events = match central.events().await {
+        Ok(events) => Some(events),
+        Err(e) => {
+            debug!("adapter events unavailable, polling instead: {}", e);
+            None
         }
+    };
+    central
+        .start_scan(ScanFilter::default())
+        .await
+        .map_err(|e| BledomError::scan(None, e))?;
+
+    let state = ScanStream {
+        manager: Some(manager),
+        central,
+        scanning: true,
+        events,
+        seen: HashSet::new(),
+        pending: Vec::new(),
+        deadline: time::Instant::now() + duration,
+    };
+    Ok(futures::stream::unfold(state, |mut state| async move {
+        let device = state.next().await?;
+        Some((device, state))
+    }))
+}
+
+struct ScanStream {
+    // Kept alive for as long as the adapter is in use.
+    manager: Option<Manager>,
+    central: Adapter,
+    scanning: bool,
+    events: Option<Pin<Box<dyn Stream<Item = CentralEvent> + Send>>>,
+    seen: HashSet<BDAddr>,
+    pending: Vec<DiscoveredDevice>,
+    deadline: time::Instant,
+}
+
+impl ScanStream {
+    async fn next(&mut self) -> Option<DiscoveredDevice> {
+        let found = time::timeout_at(self.deadline, self.next_new()).await;
+        if found.is_err() && self.scanning {
+            self.scanning = false;
+            if let Err(e) = self.central.stop_scan().await {
+                warn!("failed to stop scan: {}", e);
+            }
+        }
+        found.ok()
     }
-    Err(BledomError::DeviceNotFound)
+
+    async fn next_new(&mut self) -> DiscoveredDevice {
+        loop {
+            if let Some(device) = self.pending.pop() {
+                return device;
+            }
+            let candidates = match &mut self.events {
+                Some(events) => match events.next().await {
+                    Some(CentralEvent::DeviceDiscovered(id) | CentralEvent::DeviceUpdated(id)) => {
+                        match self.central.peripheral(&id).await {
+                            Ok(p) => discovered_device(&p).await.into_iter().collect(),
+                            Err(_) => Vec::new(),
+                        }
+                    }
+                    Some(_) => Vec::new(),
+                    None => {
+                        debug!("adapter event stream closed, polling instead");
+                        self.events = None;
+                        Vec::new()
+                    }
+                },
+                None => {
+                    time::sleep(Duration::from_millis(500)).await;
+                    collect_discovered(&self.central).await.unwrap_or_default()
+                }
+            };
+            self.pending.extend(
+                candidates
+                    .into_iter()
+                    .filter(|d| self.seen.insert(d.address))
+                    .rev(),
+            );
+        }
+    }
+}
+
+impl Drop for ScanStream {
+    fn drop(&mut self) {
+        if !self.scanning {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            warn!("scan stream dropped outside a tokio runtime, not stopping the scan");
+            return;
+        };
+        let central = self.central.clone();
+        let manager = self.manager.take();
+        runtime.spawn(async move {
+            if let Err(e) = central.stop_scan().await {
+                warn!("failed to stop scan on drop: {}", e);
+            }
+            drop(manager);
+        });
+    }
+}
+
+async fn collect_discovered(central: &Adapter) -> Result<Vec<DiscoveredDevice>, BledomError> {
+    let mut found = Vec::new();
+    for p in central.peripherals().await? {
+        found.extend(discovered_device(&p).await);
+    }
+    Ok(found)
+}
+
+async fn discovered_device(p: &Peripheral) -> Option<DiscoveredDevice> {
+    let properties = p.properties().await.ok().flatten()?;
+    let name = properties.local_name.filter(|n| n.contains(DEVICE_NAME))?;
+    Some(DiscoveredDevice {
+        address: p.address(),
+        rssi: properties.rssi,
+        name,
+    })
 }
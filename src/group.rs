@@ -0,0 +1,83 @@
+use crate::device::{BledomDevice, BledomError, Effect};
+use crate::sink::{BleSink, CommandSink};
+use futures::future::join_all;
+use std::future::Future;
+
+pub type GroupErrors = Vec<(usize, BledomError)>;
+
+// Sends every command to all members concurrently. A failing member doesn't stop the others;
+// its index in `devices` is reported alongside the error.
+#[derive(Debug)]
+pub struct BledomGroup<S = BleSink> {
+    devices: Vec<BledomDevice<S>>,
+}
+
+impl<S: CommandSink> BledomGroup<S> {
+    pub fn new(devices: Vec<BledomDevice<S>>) -> Self {
+        Self { devices }
+    }
+
+    pub fn devices(&self) -> &[BledomDevice<S>] {
+        &self.devices
+    }
+
+    pub fn into_devices(self) -> Vec<BledomDevice<S>> {
+        self.devices
+    }
+
+    async fn fan_out<'a, F, Fut>(&'a self, command: F) -> Result<(), GroupErrors>
+    where
+        F: Fn(&'a BledomDevice<S>) -> Fut,
+        Fut: Future<Output = Result<(), BledomError>>,
+    {
+        let errors: GroupErrors = join_all(self.devices.iter().map(command))
+            .await
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, result)| result.err().map(|e| (i, e)))
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub async fn power_on(&self) -> Result<(), GroupErrors> {
+        self.fan_out(|d| d.power_on()).await
+    }
+
+    pub async fn power_off(&self) -> Result<(), GroupErrors> {
+        self.fan_out(|d| d.power_off()).await
+    }
+
+    pub async fn set_brightness(&self, value: u8) -> Result<(), GroupErrors> {
+        self.fan_out(|d| d.set_brightness(value)).await
+    }
+
+    pub async fn set_color(
+        &self,
+        red_value: u8,
+        green_value: u8,
+        blue_value: u8,
+    ) -> Result<(), GroupErrors> {
+        self.fan_out(|d| d.set_color(red_value, green_value, blue_value))
+            .await
+    }
+
+    pub async fn set_color_hex(&self, hex: &str) -> Result<(), GroupErrors> {
+        self.fan_out(|d| d.set_color_hex(hex)).await
+    }
+
+    pub async fn set_effect(&self, value: u8) -> Result<(), GroupErrors> {
+        self.fan_out(|d| d.set_effect(value)).await
+    }
+
+    pub async fn set_effect_typed(&self, effect: Effect) -> Result<(), GroupErrors> {
+        self.fan_out(|d| d.set_effect_typed(effect)).await
+    }
+
+    pub async fn set_effect_speed(&self, value: u8) -> Result<(), GroupErrors> {
+        self.fan_out(|d| d.set_effect_speed(value)).await
+    }
+}
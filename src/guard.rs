@@ -0,0 +1,54 @@
+use crate::device::BledomDevice;
+use log::warn;
+use std::ops::Deref;
+
+// Powers the strip off and disconnects when dropped. Drop can't await, so this only spawns
+// the cleanup on the current tokio runtime: it is skipped when dropped outside a runtime, and
+// may never run if the runtime shuts down right after (e.g. at the end of `#[tokio::main]`).
+// Call `power_off` explicitly when it has to happen.
+#[derive(Debug)]
+pub struct PowerOffGuard {
+    device: Option<BledomDevice>,
+}
+
+impl PowerOffGuard {
+    pub fn new(device: BledomDevice) -> Self {
+        Self {
+            device: Some(device),
+        }
+    }
+
+    // Disarms the guard and hands the device back.
+    pub fn into_inner(mut self) -> BledomDevice {
+        self.device.take().expect("device is only taken on drop")
+    }
+}
+
+impl Deref for PowerOffGuard {
+    type Target = BledomDevice;
+
+    fn deref(&self) -> &BledomDevice {
+        self.device.as_ref().expect("device is only taken on drop")
+    }
+}
+
+impl Drop for PowerOffGuard {
+    fn drop(&mut self) {
+        let Some(device) = self.device.take() else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            warn!("PowerOffGuard dropped outside a tokio runtime, not powering off");
+            return;
+        };
+        runtime.spawn(async move {
+            let address = device.address();
+            if let Err(e) = device.power_off().await {
+                warn!(device:% = address; "failed to power off on drop: {}", e);
+            }
+            if let Err(e) = device.disconnect().await {
+                warn!(device:% = address; "failed to disconnect on drop: {}", e);
+            }
+        });
+    }
+}
@@ -1 +1,11 @@
+pub mod ambilight;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod colors;
+pub mod command;
 pub mod device;
+pub mod group;
+pub mod guard;
+#[cfg(feature = "remote")]
+pub mod remote;
+pub mod sink;
@@ -0,0 +1,154 @@
+use crate::device::{BledomDevice, BledomError};
+use crate::sink::{CommandSink, Delivery};
+use btleplug::api::WriteType;
+use log::{debug, warn};
+use std::io;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+// Wire format, one exchange per write:
+//   request:  <write type> <len> <packet bytes>
+//   response: <status> <len> <error message, UTF-8>
+// Write type 0 leaves it to the bridge's default; statuses follow `Delivery`, plus an error.
+const WRITE_DEFAULT: u8 = 0;
+const WRITE_WITH_RESPONSE: u8 = 1;
+const WRITE_WITHOUT_RESPONSE: u8 = 2;
+const STATUS_SENT: u8 = 0;
+const STATUS_ACKNOWLEDGED: u8 = 1;
+const STATUS_FAILED: u8 = 2;
+
+// Sends packets over TCP to a bridge running `serve` next to the strip. The bridge answers
+// every write, so errors on its side come back as `BledomError::Other` with its message. A
+// dropped connection is re-established on the next write.
+#[derive(Debug)]
+pub struct RemoteSink {
+    address: String,
+    stream: Mutex<Option<TcpStream>>,
+}
+
+impl RemoteSink {
+    // `address` is anything `TcpStream::connect` takes as a string, e.g. "raspberrypi:7878".
+    pub async fn connect(address: impl Into<String>) -> Result<Self, BledomError> {
+        let address = address.into();
+        let stream = TcpStream::connect(&address)
+            .await
+            .map_err(|e| BledomError::Other(Box::new(e)))?;
+        stream.set_nodelay(true).ok();
+        Ok(Self {
+            address,
+            stream: Mutex::new(Some(stream)),
+        })
+    }
+
+    async fn exchange(&self, write_type: u8, data: &[u8]) -> io::Result<Result<Delivery, String>> {
+        let len = u8::try_from(data.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "packet too long"))?;
+        let mut stream = self.stream.lock().await;
+        if stream.is_none() {
+            debug!("reconnecting to bridge at {}", self.address);
+            let reconnected = TcpStream::connect(&self.address).await?;
+            reconnected.set_nodelay(true).ok();
+            *stream = Some(reconnected);
+        }
+        let Some(connection) = stream.as_mut() else {
+            unreachable!("connection was just established");
+        };
+        let result = request(connection, write_type, len, data).await;
+        if result.is_err() {
+            *stream = None;
+        }
+        result
+    }
+}
+
+async fn request(
+    stream: &mut TcpStream,
+    write_type: u8,
+    len: u8,
+    data: &[u8],
+) -> io::Result<Result<Delivery, String>> {
+    let mut frame = vec![write_type, len];
+    frame.extend_from_slice(data);
+    stream.write_all(&frame).await?;
+    let status = stream.read_u8().await?;
+    let mut message = vec![0; stream.read_u8().await? as usize];
+    stream.read_exact(&mut message).await?;
+    Ok(match status {
+        STATUS_SENT => Ok(Delivery::Sent),
+        STATUS_ACKNOWLEDGED => Ok(Delivery::Acknowledged),
+        _ => Err(String::from_utf8_lossy(&message).into_owned()),
+    })
+}
+
+impl CommandSink for RemoteSink {
+    async fn send(&self, data: &[u8]) -> Result<(), BledomError> {
+        self.send_remote(WRITE_DEFAULT, data).await.map(|_| ())
+    }
+
+    async fn send_with(&self, data: &[u8], write_type: WriteType) -> Result<Delivery, BledomError> {
+        let write_type = match write_type {
+            WriteType::WithResponse => WRITE_WITH_RESPONSE,
+            WriteType::WithoutResponse => WRITE_WITHOUT_RESPONSE,
+        };
+        self.send_remote(write_type, data).await
+    }
+}
+
+impl RemoteSink {
+    async fn send_remote(&self, write_type: u8, data: &[u8]) -> Result<Delivery, BledomError> {
+        match self.exchange(write_type, data).await {
+            Ok(Ok(delivery)) => Ok(delivery),
+            Ok(Err(message)) => Err(BledomError::Other(message.into())),
+            Err(e) => Err(BledomError::Other(Box::new(e))),
+        }
+    }
+}
+
+// Bridge side of `RemoteSink`: writes every packet received on `stream` to `device` until the
+// client disconnects. Pacing is the device's own command delay.
+pub async fn serve<S: CommandSink>(
+    mut stream: TcpStream,
+    device: &BledomDevice<S>,
+) -> io::Result<()> {
+    stream.set_nodelay(true).ok();
+    loop {
+        let write_type = match stream.read_u8().await {
+            Ok(write_type) => write_type,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let mut data = vec![0; stream.read_u8().await? as usize];
+        stream.read_exact(&mut data).await?;
+
+        // Matched right away: the error isn't `Send` and must be gone before the next await.
+        let (status, message) = match bridged_write(device, write_type, &data).await {
+            Ok(Delivery::Sent) => (STATUS_SENT, String::new()),
+            Ok(Delivery::Acknowledged) => (STATUS_ACKNOWLEDGED, String::new()),
+            Err(e) => {
+                warn!("bridged write failed: {}", e);
+                (STATUS_FAILED, e.to_string())
+            }
+        };
+        let message = &message.as_bytes()[..message.len().min(u8::MAX as usize)];
+        let mut response = vec![status, message.len() as u8];
+        response.extend_from_slice(message);
+        stream.write_all(&response).await?;
+    }
+}
+
+async fn bridged_write<S: CommandSink>(
+    device: &BledomDevice<S>,
+    write_type: u8,
+    data: &[u8],
+) -> Result<Delivery, BledomError> {
+    match write_type {
+        WRITE_WITH_RESPONSE => device.write_raw_with(data, WriteType::WithResponse).await,
+        WRITE_WITHOUT_RESPONSE => {
+            device
+                .write_raw_with(data, WriteType::WithoutResponse)
+                .await
+        }
+        _ => device.write_raw(data).await.map(|()| Delivery::Sent),
+    }
+}
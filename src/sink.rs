@@ -0,0 +1,137 @@
+use crate::device::{connect_light, discover_characteristic, BledomError, RetryPolicy};
+use btleplug::api::{BDAddr, Characteristic, Peripheral as _, WriteType};
+use btleplug::platform::{Adapter, Peripheral};
+use log::{info, warn};
+use std::future::Future;
+use std::time::Duration;
+use tokio::time;
+
+// What a successful write tells us. `Acknowledged` only comes from a `WithResponse` write the
+// strip confirmed; a GATT error from the strip is returned as `Err` instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    Sent,
+    Acknowledged,
+}
+
+impl Delivery {
+    pub fn is_acknowledged(self) -> bool {
+        self == Delivery::Acknowledged
+    }
+}
+
+pub trait CommandSink {
+    fn send(&self, data: &[u8]) -> impl Future<Output = Result<(), BledomError>> + Send;
+
+    // Sinks without a notion of write types just ignore the override.
+    fn send_with(
+        &self,
+        data: &[u8],
+        write_type: WriteType,
+    ) -> impl Future<Output = Result<Delivery, BledomError>> + Send {
+        let _ = write_type;
+        let send = self.send(data);
+        async move { send.await.map(|()| Delivery::Sent) }
+    }
+
+    // Identifies the strip behind the sink in log records, where there is one.
+    fn address(&self) -> Option<BDAddr> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BleSink {
+    pub(crate) peripheral: Peripheral,
+    pub(crate) characteristic: Characteristic,
+    pub(crate) write_type: WriteType,
+    pub(crate) connection_policy: RetryPolicy,
+    pub(crate) auto_reconnect: bool,
+    pub(crate) name: Option<String>,
+    pub(crate) adapter: Option<Adapter>,
+    pub(crate) dry_run: bool,
+    pub(crate) write_timeout: Duration,
+}
+
+impl BleSink {
+    // Re-runs connect + service discovery with the builder's retry settings if the link dropped.
+    pub(crate) async fn ensure_connected(&self) -> Result<(), BledomError> {
+        if self.peripheral.is_connected().await? {
+            return Ok(());
+        }
+        warn!(device:% = self.peripheral.address(); "light disconnected, reconnecting...");
+        connect_light(&self.peripheral, self.connection_policy, None).await?;
+        discover_characteristic(&self.peripheral, self.characteristic.uuid).await?;
+        Ok(())
+    }
+}
+
+impl CommandSink for BleSink {
+    fn address(&self) -> Option<BDAddr> {
+        Some(self.peripheral.address())
+    }
+
+    async fn send(&self, data: &[u8]) -> Result<(), BledomError> {
+        self.send_with(data, self.write_type).await.map(|_| ())
+    }
+
+    async fn send_with(&self, data: &[u8], write_type: WriteType) -> Result<Delivery, BledomError> {
+        if self.dry_run {
+            info!(
+                device:% = self.peripheral.address();
+                "dry run, not writing {:02x?} ({:?})", data, write_type
+            );
+            return Ok(Delivery::Sent);
+        }
+        if self.auto_reconnect {
+            self.ensure_connected().await?;
+        }
+        let write = self
+            .peripheral
+            .write(&self.characteristic, data, write_type);
+        let Ok(result) = time::timeout(self.write_timeout, write).await else {
+            return Err(BledomError::WriteTimeout(self.write_timeout));
+        };
+        match result {
+            Ok(()) if write_type == WriteType::WithResponse => Ok(Delivery::Acknowledged),
+            Ok(()) => Ok(Delivery::Sent),
+            Err(btleplug::Error::NotConnected) => Err(BledomError::NotConnected),
+            // Stacks often report a dropped link as some generic failure; ask directly.
+            Err(e) => match self.peripheral.is_connected().await {
+                Ok(false) => Err(BledomError::NotConnected),
+                _ => Err(e.into()),
+            },
+        }
+    }
+}
+
+// Records every packet instead of writing it, so command encoding can be tested without a
+// strip. Clones share the same record.
+#[cfg(feature = "test-util")]
+#[derive(Debug, Clone, Default)]
+pub struct MockSink {
+    sent: std::sync::Arc<std::sync::Mutex<Vec<Vec<u8>>>>,
+}
+
+#[cfg(feature = "test-util")]
+impl MockSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn sent(&self) -> Vec<Vec<u8>> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    pub fn clear(&self) {
+        self.sent.lock().unwrap().clear();
+    }
+}
+
+#[cfg(feature = "test-util")]
+impl CommandSink for MockSink {
+    async fn send(&self, data: &[u8]) -> Result<(), BledomError> {
+        self.sent.lock().unwrap().push(data.to_vec());
+        Ok(())
+    }
+}
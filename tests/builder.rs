@@ -0,0 +1,43 @@
+use elk_bledom_controller::device::{BledomDevice, BledomError};
+use std::time::Duration;
+
+#[tokio::test]
+async fn zero_scan_retries_is_rejected() {
+    let result = BledomDevice::builder().scan_retries(0).build().await;
+    assert!(matches!(result, Err(BledomError::InvalidConfig(_))));
+}
+
+#[tokio::test]
+async fn zero_write_timeout_is_rejected() {
+    let result = BledomDevice::builder()
+        .write_timeout(Duration::ZERO)
+        .build()
+        .await;
+    assert!(matches!(result, Err(BledomError::InvalidConfig(_))));
+}
+
+#[tokio::test]
+async fn zero_gamma_is_rejected() {
+    let result = BledomDevice::builder().gamma(0.0).build().await;
+    assert_eq!(
+        result.err(),
+        Some(BledomError::InvalidConfig(
+            "gamma must be positive and finite, got 0".to_string()
+        ))
+    );
+}
+
+#[tokio::test]
+async fn zero_keepalive_is_rejected() {
+    let result = BledomDevice::builder()
+        .keepalive(Duration::ZERO)
+        .build()
+        .await;
+    assert!(matches!(result, Err(BledomError::InvalidConfig(_))));
+}
+
+#[tokio::test]
+async fn white_channel_color_mode_is_rejected() {
+    let result = BledomDevice::builder().color_mode_byte(0x01).build().await;
+    assert!(matches!(result, Err(BledomError::InvalidConfig(_))));
+}
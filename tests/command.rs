@@ -0,0 +1,186 @@
+use elk_bledom_controller::command::{Command, COLOR_MODE_RGB};
+use elk_bledom_controller::device::{BledomDevice, Schedule, ScheduleKind};
+use elk_bledom_controller::sink::MockSink;
+
+#[tokio::test]
+async fn emitted_packets_decode() {
+    let sink = MockSink::new();
+    let device = BledomDevice::builder()
+        .command_delay_ms(0)
+        .build_with_sink(sink.clone());
+    device
+        .batch()
+        .power_on()
+        .color(1, 2, 3)
+        .brightness(40)
+        .send()
+        .await
+        .unwrap();
+    let decoded: Vec<Command> = sink
+        .sent()
+        .iter()
+        .map(|packet| Command::try_from(packet.as_slice()).unwrap())
+        .collect();
+    assert_eq!(
+        decoded,
+        vec![
+            Command::PowerOn,
+            Command::SetColor(1, 2, 3),
+            Command::SetBrightness(40)
+        ]
+    );
+}
+
+#[test]
+fn unknown_packet_is_rejected() {
+    let packet = [0x7e, 0x00, 0x99, 0x00, 0x00, 0x00, 0x00, 0x00, 0xef];
+    assert!(Command::try_from(&packet[..]).is_err());
+    assert!(Command::try_from(&packet[..8]).is_err());
+}
+
+#[test]
+fn encode_decode_round_trip() {
+    let commands = [
+        Command::PowerOn,
+        Command::PowerOff,
+        Command::SetColor(10, 20, 30),
+        Command::SetColorWithMode {
+            mode: 0x00,
+            red: 40,
+            green: 50,
+            blue: 60,
+        },
+        Command::SetWhite(200),
+        Command::SetBrightness(100),
+        Command::SetEffect(0x95),
+        Command::SetEffectSpeed(0),
+        Command::SetTime {
+            hour: 23,
+            minute: 59,
+            second: 1,
+            day_of_week: 7,
+        },
+        Command::SetSchedule {
+            kind: ScheduleKind::Off,
+            schedule: Schedule {
+                days: 0x15,
+                hour: 7,
+                minute: 30,
+                enabled: true,
+            },
+        },
+        Command::EnableMic(true),
+        Command::SetMusicMode(0x82),
+        Command::SetMusicSensitivity(60),
+    ];
+    for command in commands {
+        assert_eq!(Command::try_from(&command.encode()[..]).unwrap(), command);
+    }
+    // These would encode to `SetColor`/`SetWhite` packets, so they can't round-trip.
+    for mode in [COLOR_MODE_RGB, 0x01] {
+        let command = Command::SetColorWithMode {
+            mode,
+            red: 40,
+            green: 0,
+            blue: 0,
+        };
+        assert!(command.validate().is_err());
+    }
+}
+
+// Every value of every byte-sized parameter, standing in for property tests: packets are
+// always framed, `validate` accepts exactly the documented ranges, and decoding restores
+// the command.
+fn assert_invariants(command: Command, valid: bool) {
+    let packet = command.encode();
+    assert_eq!(packet.len(), 9);
+    assert_eq!(packet[0], 0x7e, "{command:?}");
+    assert_eq!(packet[8], 0xef, "{command:?}");
+    assert_eq!(command.validate().is_ok(), valid, "{command:?}");
+    assert_eq!(Command::try_from(&packet[..]).unwrap(), command);
+}
+
+#[test]
+fn single_value_commands_hold_invariants() {
+    for value in 0..=u8::MAX {
+        assert_invariants(Command::SetBrightness(value), value <= 100);
+        assert_invariants(Command::SetEffectSpeed(value), value <= 100);
+        assert_invariants(Command::SetMusicSensitivity(value), value <= 100);
+        assert_invariants(Command::SetEffect(value), true);
+        assert_invariants(Command::SetWhite(value), true);
+        assert_invariants(Command::SetMusicMode(value), true);
+        assert_invariants(Command::SetColor(value, 0, 0), true);
+        assert_invariants(Command::SetColor(0, value, 0), true);
+        assert_invariants(Command::SetColor(0, 0, value), true);
+    }
+}
+
+#[test]
+fn time_commands_hold_invariants() {
+    for a in 0..=u8::MAX {
+        for b in 0..=u8::MAX {
+            let time = Command::SetTime {
+                hour: a,
+                minute: b,
+                second: 0,
+                day_of_week: 1,
+            };
+            assert_invariants(time, a <= 23 && b <= 59);
+            let time = Command::SetTime {
+                hour: 0,
+                minute: 0,
+                second: a,
+                day_of_week: b,
+            };
+            assert_invariants(time, a <= 59 && (1..=7).contains(&b));
+        }
+    }
+}
+
+#[test]
+fn schedule_commands_hold_invariants() {
+    for days in 0..=0x7f {
+        for kind in [ScheduleKind::On, ScheduleKind::Off] {
+            for enabled in [false, true] {
+                let schedule = Schedule {
+                    days,
+                    hour: 12,
+                    minute: 30,
+                    enabled,
+                };
+                assert_invariants(Command::SetSchedule { kind, schedule }, true);
+            }
+        }
+    }
+    for hour in 0..=u8::MAX {
+        let schedule = Schedule {
+            days: 0x7f,
+            hour,
+            minute: 0,
+            enabled: true,
+        };
+        let command = Command::SetSchedule {
+            kind: ScheduleKind::On,
+            schedule,
+        };
+        assert_invariants(command, hour <= 23);
+    }
+}
+
+#[tokio::test]
+async fn typed_methods_only_send_valid_packets() {
+    let sink = MockSink::new();
+    let device = BledomDevice::builder()
+        .command_delay_ms(0)
+        .build_with_sink(sink.clone());
+    for value in 0..=u8::MAX {
+        assert_eq!(device.set_brightness(value).await.is_ok(), value <= 100);
+        assert_eq!(device.set_effect_speed(value).await.is_ok(), value <= 100);
+    }
+    let sent = sink.sent();
+    assert_eq!(sent.len(), 2 * 101);
+    for packet in sent {
+        let packet: [u8; 9] = packet.try_into().unwrap();
+        assert_eq!(Command::try_from(&packet[..]).unwrap().encode(), packet);
+    }
+}
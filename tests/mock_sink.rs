@@ -0,0 +1,297 @@
+use btleplug::api::WriteType;
+use elk_bledom_controller::command::Command;
+use elk_bledom_controller::device::{BledomDevice, Effect, EffectScene, LightMode, Percent};
+use elk_bledom_controller::sink::{Delivery, MockSink};
+use std::time::Duration;
+
+fn mock_device() -> (BledomDevice<MockSink>, MockSink) {
+    let sink = MockSink::new();
+    let device = BledomDevice::builder()
+        .command_delay_ms(0)
+        .build_with_sink(sink.clone());
+    (device, sink)
+}
+
+#[tokio::test]
+async fn set_color_encodes_rgb_packet() {
+    let (device, sink) = mock_device();
+    device.set_color(255, 0, 0).await.unwrap();
+    assert_eq!(
+        sink.sent(),
+        vec![vec![0x7e, 0x00, 0x05, 0x03, 0xff, 0x00, 0x00, 0x00, 0xef]]
+    );
+}
+
+#[tokio::test]
+async fn invalid_brightness_sends_nothing() {
+    let (device, sink) = mock_device();
+    assert!(device.set_brightness(101).await.is_err());
+    assert!(sink.sent().is_empty());
+}
+
+#[tokio::test]
+async fn batch_sends_commands_in_order() {
+    let (device, sink) = mock_device();
+    device
+        .batch()
+        .power_on()
+        .brightness(50)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        sink.sent(),
+        vec![
+            vec![0x7e, 0x00, 0x04, 0xf0, 0x00, 0x01, 0xff, 0x00, 0xef],
+            vec![0x7e, 0x00, 0x01, 0x32, 0x00, 0x00, 0x00, 0x00, 0xef],
+        ]
+    );
+}
+
+#[tokio::test]
+async fn current_state_tracks_last_write() {
+    let (device, _sink) = mock_device();
+    assert_eq!(device.current_color(), None);
+    assert_eq!(device.last_command(), None);
+    device.set_color(1, 2, 3).await.unwrap();
+    device.set_brightness(40).await.unwrap();
+    assert_eq!(device.current_color(), Some((1, 2, 3)));
+    assert_eq!(device.current_brightness(), Some(40));
+    assert_eq!(
+        device.last_command(),
+        Some([0x7e, 0x00, 0x01, 40, 0x00, 0x00, 0x00, 0x00, 0xef])
+    );
+}
+
+#[tokio::test]
+async fn current_color_is_reported_before_gamma_correction() {
+    let sink = MockSink::new();
+    let device = BledomDevice::builder()
+        .command_delay_ms(0)
+        .gamma(2.2)
+        .build_with_sink(sink.clone());
+    device.set_color(128, 128, 128).await.unwrap();
+    device.set_effect(0x96).await.unwrap();
+    device.stop_effect().await.unwrap();
+    assert_eq!(device.current_color(), Some((128, 128, 128)));
+    let wire = &sink.sent()[0];
+    assert_eq!(sink.sent().last(), Some(wire));
+    assert!(wire[4] < 128);
+}
+
+#[tokio::test]
+async fn sinks_without_write_types_report_sent() {
+    let (device, sink) = mock_device();
+    let delivery = device
+        .power_off_with(WriteType::WithResponse)
+        .await
+        .unwrap();
+    assert_eq!(delivery, Delivery::Sent);
+    assert_eq!(sink.sent().len(), 1);
+}
+
+#[tokio::test]
+async fn color_mode_byte_overrides_rgb_mode() {
+    let sink = MockSink::new();
+    let device = BledomDevice::builder()
+        .command_delay_ms(0)
+        .color_mode_byte(0x00)
+        .build_with_sink(sink.clone());
+    device.set_color(255, 0, 0).await.unwrap();
+    assert_eq!(
+        sink.sent(),
+        vec![vec![0x7e, 0x00, 0x05, 0x00, 0xff, 0x00, 0x00, 0x00, 0xef]]
+    );
+    assert_eq!(device.current_color(), Some((255, 0, 0)));
+}
+
+#[tokio::test]
+async fn coalescing_skips_superseded_updates() {
+    let sink = MockSink::new();
+    let device = BledomDevice::builder()
+        .command_delay_ms(10)
+        .coalesce_updates(true)
+        .build_with_sink(sink.clone());
+    let updates = (1..=5).map(|value| device.set_brightness(value));
+    for result in futures::future::join_all(updates).await {
+        result.unwrap();
+    }
+    assert_eq!(
+        sink.sent(),
+        vec![
+            vec![0x7e, 0x00, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0xef],
+            vec![0x7e, 0x00, 0x01, 0x05, 0x00, 0x00, 0x00, 0x00, 0xef],
+        ]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn coalesced_update_survives_cancelled_newer_call() {
+    let sink = MockSink::new();
+    let device = BledomDevice::builder()
+        .command_delay_ms(50)
+        .coalesce_updates(true)
+        .build_with_sink(sink.clone());
+    let (first, second, third) = tokio::join!(
+        device.set_brightness(10),
+        device.set_brightness(20),
+        tokio::time::timeout(Duration::from_millis(10), device.set_brightness(30)),
+    );
+    first.unwrap();
+    second.unwrap();
+    assert!(third.is_err());
+    let levels: Vec<u8> = sink.sent().iter().map(|packet| packet[3]).collect();
+    assert_eq!(levels, vec![10, 30]);
+}
+
+#[tokio::test]
+async fn brightness_255_is_rounded_to_percent() {
+    let (device, sink) = mock_device();
+    for value in [0, 128, 255] {
+        device.set_brightness_255(value).await.unwrap();
+    }
+    let levels: Vec<u8> = sink.sent().iter().map(|packet| packet[3]).collect();
+    assert_eq!(levels, vec![0, 50, 100]);
+}
+
+#[tokio::test]
+async fn power_on_fade_dims_before_powering_on() {
+    let (device, sink) = mock_device();
+    device
+        .power_on_fade(Percent::new(80).unwrap(), Duration::from_millis(0))
+        .await
+        .unwrap();
+    let sent = sink.sent();
+    assert_eq!(
+        sent[0],
+        [0x7e, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xef]
+    );
+    assert_eq!(
+        sent[1],
+        [0x7e, 0x00, 0x04, 0xf0, 0x00, 0x01, 0xff, 0x00, 0xef]
+    );
+    assert_eq!(sent.last().unwrap()[3], 80);
+}
+
+#[tokio::test]
+async fn color_after_effect_resets_to_static() {
+    let (device, sink) = mock_device();
+    device.set_effect(0x96).await.unwrap();
+    assert_eq!(device.current_mode(), Some(LightMode::Effect));
+    device.set_color(0, 0, 255).await.unwrap();
+    device.set_color(0, 255, 0).await.unwrap();
+    let sent = sink.sent();
+    assert_eq!(sent.len(), 4);
+    assert_eq!(
+        sent[1],
+        [0x7e, 0x00, 0x04, 0xf0, 0x00, 0x01, 0xff, 0x00, 0xef]
+    );
+    assert_eq!(device.current_mode(), Some(LightMode::Static));
+
+    sink.clear();
+    device.set_effect(0x96).await.unwrap();
+    device.set_color_with(255, 0, 0, false).await.unwrap();
+    assert_eq!(sink.sent().len(), 2);
+}
+
+#[tokio::test]
+async fn color_after_effect_and_power_off_keeps_strip_off() {
+    let (device, sink) = mock_device();
+    device.set_effect(0x96).await.unwrap();
+    device.power_off().await.unwrap();
+    assert_eq!(device.current_mode(), None);
+    sink.clear();
+    device.set_color(0, 0, 255).await.unwrap();
+    assert_eq!(
+        sink.sent(),
+        vec![vec![0x7e, 0x00, 0x05, 0x03, 0x00, 0x00, 0xff, 0x00, 0xef]]
+    );
+}
+
+#[tokio::test]
+async fn batch_color_after_effect_resets_to_static() {
+    let (device, sink) = mock_device();
+    device.set_effect(0x96).await.unwrap();
+    sink.clear();
+    device
+        .set_color_brightness((0, 0, 255), Percent::new(50).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(
+        sink.sent(),
+        vec![
+            Command::SetBrightness(50).encode().to_vec(),
+            Command::PowerOn.encode().to_vec(),
+            Command::SetColor(0, 0, 255).encode().to_vec(),
+        ]
+    );
+    assert_eq!(device.current_mode(), Some(LightMode::Static));
+}
+
+#[tokio::test]
+async fn effect_scene_sends_effect_speed_then_brightness() {
+    let (device, sink) = mock_device();
+    let scene = EffectScene {
+        effect: Effect::BlinkRed,
+        speed: Percent::new(30).unwrap(),
+        brightness: Percent::new(70).unwrap(),
+    };
+    device.apply_effect_scene(&scene).await.unwrap();
+    let kinds: Vec<(u8, u8)> = sink.sent().iter().map(|p| (p[2], p[3])).collect();
+    assert_eq!(kinds, vec![(0x03, 0x96), (0x02, 30), (0x01, 70)]);
+}
+
+#[tokio::test]
+async fn effect_speed_ramp_ends_on_target() {
+    let sink = MockSink::new();
+    let device = BledomDevice::builder()
+        .command_delay_ms(10)
+        .build_with_sink(sink.clone());
+    device
+        .ramp_effect_speed(
+            Percent::new(10).unwrap(),
+            Percent::new(90).unwrap(),
+            Duration::from_millis(40),
+        )
+        .await
+        .unwrap();
+    let speeds: Vec<u8> = sink.sent().iter().map(|packet| packet[3]).collect();
+    assert_eq!(speeds, vec![10, 30, 50, 70, 90]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn keepalive_pings_only_after_idle_interval() {
+    let (mut device, sink) = mock_device();
+    device.start_keepalive(Duration::from_secs(10)).unwrap();
+    device.set_brightness(40).await.unwrap();
+    for _ in 0..6 {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        device.set_color(1, 2, 3).await.unwrap();
+    }
+    let ping = Command::SetBrightness(40).encode().to_vec();
+    assert_eq!(sink.sent().iter().filter(|p| **p == ping).count(), 1);
+
+    sink.clear();
+    tokio::time::sleep(Duration::from_secs(11)).await;
+    assert_eq!(sink.sent(), vec![ping]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn keepalive_without_brightness_resends_power_on() {
+    let sink = MockSink::new();
+    let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let record = std::sync::Arc::clone(&seen);
+    let mut device = BledomDevice::builder()
+        .command_delay_ms(0)
+        .on_command(Box::new(move |packet| {
+            record.lock().unwrap().push(packet.to_vec())
+        }))
+        .build_with_sink(sink.clone());
+    device.start_keepalive(Duration::from_secs(10)).unwrap();
+    device.power_on().await.unwrap();
+    sink.clear();
+    tokio::time::sleep(Duration::from_secs(11)).await;
+    let power_on = Command::PowerOn.encode().to_vec();
+    assert_eq!(sink.sent(), vec![power_on.clone()]);
+    assert_eq!(seen.lock().unwrap().last(), Some(&power_on));
+}
@@ -0,0 +1,31 @@
+use elk_bledom_controller::device::BledomDevice;
+use elk_bledom_controller::remote::{serve, RemoteSink};
+use elk_bledom_controller::sink::MockSink;
+use tokio::net::TcpListener;
+
+#[tokio::test]
+async fn commands_reach_the_bridged_device() {
+    let bridged = MockSink::new();
+    let device = BledomDevice::builder()
+        .command_delay_ms(0)
+        .build_with_sink(bridged.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        serve(stream, &device).await.unwrap();
+    });
+
+    let remote = BledomDevice::builder()
+        .command_delay_ms(0)
+        .build_with_sink(RemoteSink::connect(address).await.unwrap());
+    remote.set_color(255, 0, 0).await.unwrap();
+    remote.power_off().await.unwrap();
+    assert_eq!(
+        bridged.sent(),
+        vec![
+            vec![0x7e, 0x00, 0x05, 0x03, 0xff, 0x00, 0x00, 0x00, 0xef],
+            vec![0x7e, 0x00, 0x04, 0x00, 0x00, 0x00, 0xff, 0x00, 0xef],
+        ]
+    );
+}